@@ -1,19 +1,45 @@
 use std::{
-    collections::BTreeMap,
-    env,
-    fs::{self, File},
-    io,
-    path::Path,
+    collections::{BTreeMap, VecDeque},
+    env, fs,
+    path::{Path, PathBuf},
     process::Command,
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
+use ratatui::widgets::TableState;
 use serde::{Deserialize, Serialize};
-use serde_json::to_writer_pretty;
+use serde_json::{to_vec_pretty, Value};
+
+use crate::backup::{self, Backup, BackupPolicy};
+use crate::checks::Report;
+use crate::config::{self, CONFIG_VERSION};
+use crate::detect;
+use crate::error::Error;
+use crate::files::copy_dir;
+use crate::inbox::Inbox;
+use crate::install::InstallPlan;
+use crate::launch::{LaunchContext, LaunchMode, LaunchTemplate, TERMINAL_PRESETS, WRAPPER_PRESETS};
+use crate::lock::Lock;
+use crate::logs::{self, LogLevel, SmapiLog};
+use crate::mods::ModScan;
+use crate::saves::{self, SaveGame, SavesSwap};
+use crate::session::{self, Session, SessionStatus};
+use crate::summary::{self, SessionSummary};
+
+/// How long headless sessions get to exit when the manager quits.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Instance {
-    folder_name: String,
-    smapi_path: Option<String>,
+    pub folder_name: String,
+    pub smapi_path: Option<String>,
+    /// Overrides the global launch template for this instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch: Option<LaunchTemplate>,
+    /// Linked in place of the game's `Saves` folder while the instance runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saves_dir: Option<String>,
 }
 
 impl Instance {
@@ -21,58 +47,394 @@ impl Instance {
         Instance {
             folder_name,
             smapi_path,
+            launch: None,
+            saves_dir: None,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Manager {
+    #[serde(default)]
+    pub version: u64,
     pub instances: BTreeMap<String, Instance>,
     pub smapi_path: String,
+    /// Downloads folder watched for new mod archives.
+    #[serde(default)]
+    pub inbox: Option<String>,
+    /// Used by every instance without its own template.
+    pub launch: LaunchTemplate,
+    /// How saves are backed up before launching.
+    pub backups: BackupPolicy,
+    /// File the config was loaded from and gets saved to.
+    #[serde(skip)]
+    pub config_path: PathBuf,
 }
 
 impl Manager {
-    pub fn new(smapi_path: String) -> Manager {
+    pub fn new(smapi_path: String, config_path: PathBuf) -> Manager {
         let mut app = Manager {
+            version: CONFIG_VERSION,
             smapi_path,
             instances: BTreeMap::new(),
+            inbox: None,
+            launch: LaunchTemplate::default(),
+            backups: BackupPolicy::default(),
+            config_path,
         };
         let instance = Instance::new("Mods".into(), None);
         app.instances.insert("Default".into(), instance);
         app
     }
 
-    pub fn load_config() -> Result<Manager, String> {
-        let contents = match fs::read_to_string("./config.json") {
+    /// Reads and migrates the config at `path` without saving it anywhere.
+    fn read_config(path: &Path) -> Result<(Manager, bool), Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::ConfigIo(format!("{}: {e}", path.display()))),
+        };
+        let mut config: Value = match serde_json::from_str(contents.as_str()) {
             Ok(c) => c,
-            Err(e) => return Err(e.to_string()),
+            Err(e) => return Err(Error::Parse(format!("{}: {e}", path.display()))),
         };
-        let manager: Manager = match serde_json::from_str(contents.as_str()) {
+        let migrated = config::migrate(&mut config, path)?;
+        let manager: Manager = match serde_json::from_value(config) {
             Ok(m) => m,
-            Err(e) => return Err(e.to_string()),
+            Err(e) => return Err(Error::Parse(format!("{}: {e}", path.display()))),
         };
+        Ok((manager, migrated))
+    }
+
+    pub fn load_config(config_path: &Path) -> Result<Manager, Error> {
+        let (mut manager, migrated) = Manager::read_config(config_path)?;
+        manager.config_path = config_path.to_path_buf();
+        if migrated {
+            manager.save()?;
+        }
         Ok(manager)
     }
 
-    pub fn run(&self, instance: Instance) {
-        let path = instance.smapi_path.unwrap_or(self.smapi_path.clone());
-        let terminal = env::var("TERMINAL").unwrap_or("konsole".into());
-
-        let mut shell = Command::new(terminal)
-            .args([
-                "-e",
-                "steam-run",
-                path.as_str(),
-                "--mods-path",
-                instance.folder_name.as_str(),
-            ])
-            .spawn()
-            .expect("Failed to spawn shell for smapi");
+    /// Like `load_config`, but falls back to the newest backup that still loads
+    /// when the config is corrupt. Returns a warning if a backup was used.
+    pub fn load_or_recover(config_path: &Path) -> Result<(Manager, Option<String>), Error> {
+        let error = match Manager::load_config(config_path) {
+            Ok(manager) => return Ok((manager, None)),
+            Err(e) => e,
+        };
+        if config::is_from_newer_version(config_path) {
+            return Err(error);
+        }
+        for backup in config::backups(config_path) {
+            if let Ok((mut manager, _)) = Manager::read_config(&backup) {
+                manager.config_path = config_path.to_path_buf();
+                manager.save()?;
+                let warning = format!(
+                    "{} could not be loaded ({error}), restored {}",
+                    config_path.display(),
+                    backup.display()
+                );
+                return Ok((manager, Some(warning)));
+            }
+        }
+        Err(error)
+    }
+
+    /// Replaces the config with its newest backup. The replaced config becomes
+    /// the newest backup, so a restore can be undone by restoring again.
+    pub fn restore_previous_config(config_path: &Path) -> Result<PathBuf, Error> {
+        for backup in config::backups(config_path) {
+            if let Ok((mut manager, _)) = Manager::read_config(&backup) {
+                manager.config_path = config_path.to_path_buf();
+                manager.save()?;
+                return Ok(backup);
+            }
+        }
+        Err(Error::MissingPath(format!(
+            "no usable backup of {}",
+            config_path.display()
+        )))
+    }
+
+    pub fn get_instance(&self, name: &str) -> Result<Instance, Error> {
+        match self.instances.get(name) {
+            Some(instance) => Ok(instance.clone()),
+            None => Err(Error::Instance(format!("no instance named {name}"))),
+        }
+    }
+
+    pub fn launch_template(&self, instance: &Instance) -> LaunchTemplate {
+        instance.launch.clone().unwrap_or(self.launch.clone())
+    }
+
+    pub fn launch_context(&self, name: &str, instance: Instance) -> LaunchContext {
+        LaunchContext {
+            smapi: instance
+                .smapi_path
+                .clone()
+                .unwrap_or(self.smapi_path.clone()),
+            mods: self.get_path(instance),
+            instance: name.to_string(),
+        }
+    }
+
+    fn launch_command(&self, name: &str) -> Result<Command, Error> {
+        let instance = self.get_instance(name)?;
+        let template = self.launch_template(&instance);
+        template.command(&self.launch_context(name, instance))
+    }
+
+    pub fn mods_path(&self, instance: Instance) -> PathBuf {
+        PathBuf::from(self.get_path(instance))
+    }
+
+    /// The lock on the mods folder of instance `name`, if something still runs from it.
+    pub fn running_lock(&self, name: &str) -> Result<Option<Lock>, Error> {
+        let instance = self.get_instance(name)?;
+        Ok(Lock::read(&self.mods_path(instance)))
+    }
+
+    /// Fails while something runs from the mods folder of `instance`, which
+    /// must not be moved, deleted or changed then.
+    fn ensure_not_running(&self, instance: &Instance) -> Result<(), Error> {
+        match Lock::read(&self.mods_path(instance.clone())) {
+            Some(lock) => Err(Error::Instance(format!(
+                "{} (pid {}) is running from {}, stop it first",
+                lock.instance,
+                lock.pid,
+                self.get_path(instance.clone())
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Folder of the config, which also holds the saves swap and the record
+    /// of played saves, so separate configs don't share them.
+    pub fn state_dir(&self) -> PathBuf {
+        match self.config_path.parent() {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        }
+    }
+
+    /// Save backups go to the data directory for the default config and next
+    /// to the config otherwise.
+    pub fn backup_dir(&self) -> PathBuf {
+        if self.config_path == config::default_config_path() {
+            config::data_dir().join("backups")
+        } else {
+            self.state_dir().join("backups")
+        }
+    }
+
+    /// Fails while a game runs from the mods folder of any instance, which
+    /// would write its saves into the swapped in folder.
+    fn ensure_no_game_running(&self) -> Result<(), Error> {
+        for instance in self.instances.values() {
+            if let Some(lock) = Lock::read(&self.mods_path(instance.clone())) {
+                return Err(Error::Instance(format!(
+                    "{} (pid {}) is still running, launch again after it exits",
+                    lock.instance, lock.pid
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Swaps in the saves of instance `name` if it has its own. While another
+    /// instance's saves are swapped in nothing can be launched, as it would
+    /// play on them, and no swap is made while any other game runs. The swap
+    /// lasts as long as the launched process, so it needs headless mode: some
+    /// terminals return while the game still runs.
+    fn swap_saves(&self, name: &str) -> Result<Option<SavesSwap>, Error> {
+        let instance = self.get_instance(name)?;
+        match instance.saves_dir.clone() {
+            Some(_) if self.launch_template(&instance).mode != LaunchMode::Headless => {
+                Err(Error::Launch(format!(
+                    "{name} has its own saves folder, which needs the headless launch mode"
+                )))
+            }
+            Some(saves) => {
+                saves::ensure_shared(&self.state_dir())?;
+                self.ensure_no_game_running()?;
+                Ok(Some(SavesSwap::swap_in(
+                    &self.state_dir(),
+                    name,
+                    &expand_home(&saves),
+                )?))
+            }
+            None => {
+                saves::ensure_shared(&self.state_dir())?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// The folder holding the saves `instance` plays on.
+    pub fn saves_root(&self, instance: &Instance) -> PathBuf {
+        match &instance.saves_dir {
+            Some(saves) => expand_home(saves),
+            None => saves::saves_dir(),
+        }
+    }
+
+    /// Every save, shared ones first and then those of each instance with its
+    /// own saves folder.
+    pub fn list_saves(&self) -> Vec<SaveGame> {
+        let state_dir = self.state_dir();
+        let mut all = saves::list_saves(
+            &state_dir,
+            &saves::shared_saves(&state_dir),
+            &saves::saves_dir(),
+            None,
+        );
+        for (name, instance) in &self.instances {
+            if instance.saves_dir.is_some() {
+                let root = self.saves_root(instance);
+                all.extend(saves::list_saves(
+                    &state_dir,
+                    &root,
+                    &root,
+                    Some(name.clone()),
+                ));
+            }
+        }
+        all
+    }
+
+    /// Name the backups of the saves `instance` plays on are kept under.
+    pub fn saves_owner(&self, name: &str, instance: &Instance) -> String {
+        match instance.saves_dir {
+            Some(_) => name.to_string(),
+            None => backup::SHARED_OWNER.to_string(),
+        }
+    }
+
+    /// Backs up the saves instance `name` is about to play on.
+    pub fn back_up_saves(&self, name: &str) -> Result<usize, Error> {
+        let instance = self.get_instance(name)?;
+        backup::back_up_all(
+            &self.backup_dir(),
+            &self.saves_owner(name, &instance),
+            &self.saves_root(&instance),
+            &self.backups,
+        )
+    }
+
+    /// Rolls a save back to `backup`. Refused while an instance plays on its
+    /// own saves, as the shared ones are moved aside then.
+    pub fn restore_save(&self, backup: &Backup) -> Result<(), Error> {
+        saves::ensure_shared(&self.state_dir())?;
+        let root = match backup.owner.as_str() {
+            backup::SHARED_OWNER => saves::saves_dir(),
+            owner => self.saves_root(&self.get_instance(owner)?),
+        };
+        backup::restore(&self.backup_dir(), backup, &root, &self.backups)
+    }
+
+    /// Swaps in the saves of instance `name` and backs them up, undoing the
+    /// swap if the backup fails.
+    fn prepare_saves(&self, name: &str) -> Result<Option<SavesSwap>, Error> {
+        let swap = self.swap_saves(name)?;
+        if let Err(e) = self.back_up_saves(name) {
+            if let Some(swap) = swap {
+                swap.restore()?;
+            }
+            return Err(Error::Launch(format!("couldn't back up the saves ({e})")));
+        }
+        Ok(swap)
+    }
+
+    /// Remembers which saves instance `name` played in the session that
+    /// started at `started`. Must run after its saves were swapped back and
+    /// its lock released. Nothing is recorded if `overlapped`, meaning another
+    /// game played on the same saves meanwhile, or one still does: which of
+    /// them wrote a save can't be told then.
+    pub fn record_saves(
+        &self,
+        name: &str,
+        started: SystemTime,
+        overlapped: bool,
+    ) -> Result<(), Error> {
+        let instance = self.get_instance(name)?;
+        let root = self.saves_root(&instance);
+        let still_playing = self.instances.values().any(|other| {
+            self.saves_root(other) == root && Lock::read(&self.mods_path(other.clone())).is_some()
+        });
+        if overlapped || still_playing {
+            return Ok(());
+        }
+        saves::record_played(
+            &self.state_dir(),
+            name,
+            &self.saves_root(&instance),
+            started,
+        )
+    }
+
+    /// Starts SMAPI in a terminal, or in the current one in headless mode, and
+    /// waits for it to exit.
+    pub fn run(&self, name: &str) -> Result<SessionSummary, Error> {
+        let mods = self.mods_path(self.get_instance(name)?);
+        let mut command = self.launch_command(name)?;
+        let mut swap = self.prepare_saves(name)?;
+        let started = SystemTime::now();
+        let mut shell = match command.spawn() {
+            Ok(shell) => shell,
+            Err(e) => {
+                if let Some(swap) = swap {
+                    swap.restore()?;
+                }
+                return Err(Error::Launch(format!(
+                    "{}: {e}",
+                    command.get_program().to_string_lossy()
+                )));
+            }
+        };
+        // a mods folder that doesn't exist yet has nothing to protect
+        let _ = Lock::write(&mods, shell.id(), name.to_string());
+        if let Some(swap) = swap.as_mut() {
+            // without the pid the swap is still undone on exit, just not
+            // protected against other launches
+            let _ = swap.set_pid(shell.id());
+        }
         let result = shell.wait();
-        match result {
-            Ok(_) => (),
-            Err(e) => println!("{e}"),
+        Lock::release(&mods, shell.id());
+        let restored = match swap {
+            Some(swap) => swap.restore(),
+            None => Ok(()),
+        };
+        // losing track of the saves played isn't worth failing over
+        let _ = self.record_saves(name, started, false);
+        let summary = match result {
+            Ok(status) => summary::finish_session(name, &mods, started, status.code(), false)?,
+            Err(e) => return Err(Error::Launch(e.to_string())),
+        };
+        restored?;
+        Ok(summary)
+    }
+
+    /// Starts SMAPI without waiting for it, capturing the output of headless
+    /// launches.
+    pub fn start(&self, name: &str) -> Result<Session, Error> {
+        let instance = self.get_instance(name)?;
+        let headless = self.launch_template(&instance).mode == LaunchMode::Headless;
+        let mods = self.mods_path(instance);
+        let command = self.launch_command(name)?;
+        let mut swap = self.prepare_saves(name)?;
+        let session = match Session::spawn(name.to_string(), mods.clone(), command, headless) {
+            Ok(session) => session,
+            Err(e) => {
+                if let Some(swap) = swap {
+                    swap.restore()?;
+                }
+                return Err(e);
+            }
+        };
+        let _ = Lock::write(&mods, session.pid(), name.to_string());
+        if let Some(swap) = swap.as_mut() {
+            let _ = swap.set_pid(session.pid());
         }
+        Ok(session)
     }
 
     pub fn get_path(&self, instance: Instance) -> String {
@@ -84,58 +446,201 @@ impl Manager {
         path
     }
 
-    pub fn open(&self, instance: Instance) {
+    /// Opens the mod folder in the file manager, creating it first if needed.
+    pub fn open(&self, instance: Instance) -> Result<(), Error> {
         let path = self.get_path(instance);
         let path = Path::new(path.as_str());
 
         if !path.exists() {
-            fs::create_dir(path).expect("failed to create folder");
+            if let Err(e) = fs::create_dir_all(path) {
+                return Err(Error::Filesystem(format!("{}: {e}", path.display())));
+            }
         }
 
-        // TODO: check if folder exists and create if not
+        match Command::new("xdg-open").arg(path).output() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Launch(format!("xdg-open: {e}"))),
+        }
+    }
 
-        let shell = Command::new("xdg-open")
-            .arg(path)
-            .output()
-            .expect("failed to open folder");
+    pub fn scan_mods(&self, instance: Instance) -> ModScan {
+        let path = self.get_path(instance);
+        ModScan::scan(Path::new(path.as_str()))
+    }
+
+    pub fn plan_install(&self, instance: Instance, archive: &Path) -> Result<InstallPlan, Error> {
+        self.ensure_not_running(&instance)?;
+        let path = self.get_path(instance.clone());
+        let installed = self.scan_mods(instance);
+        InstallPlan::read(archive, Path::new(path.as_str()), &installed)
+    }
+
+    /// Fails if a new instance can't be called `name`: another one is, or the
+    /// backups of the shared saves go by it.
+    pub fn check_new_name(&self, name: &str) -> Result<(), Error> {
+        if name == backup::SHARED_OWNER {
+            return Err(Error::Instance(format!(
+                "{name} is reserved for the backups of the shared saves"
+            )));
+        }
+        if self.instances.contains_key(name) {
+            return Err(Error::Instance(format!(
+                "an instance named {name} already exists"
+            )));
+        }
+        Ok(())
     }
 
     pub fn add_instance(&mut self, instance: Instance, name: String) {
         self.instances.insert(name, instance);
     }
 
-    pub fn save(&self) {
-        let f = File::create("./config.json").unwrap();
-        to_writer_pretty(f, &self).expect("Failed to write to file");
+    /// Copies the folder of instance `source` to `folder_name` and registers it
+    /// as `name`, optionally copying only manifests and config files.
+    pub fn clone_instance(
+        &mut self,
+        source: String,
+        name: String,
+        folder_name: String,
+        configs_only: bool,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<(), Error> {
+        self.check_new_name(&name)?;
+        let source = self.get_instance(&source)?;
+        let mut instance = Instance::new(folder_name, source.smapi_path.clone());
+        instance.launch = source.launch.clone();
+
+        let source_path = self.get_path(source);
+        let target_path = self.get_path(instance.clone());
+        copy_dir(
+            Path::new(source_path.as_str()),
+            Path::new(target_path.as_str()),
+            configs_only,
+            progress,
+        )?;
+
+        self.add_instance(instance, name);
+        self.save()
     }
 
-    pub fn remove_instance(&mut self, name: String, with_folder: bool) {
+    /// Replaces instance `old_name` with `instance` under `new_name`, moving the
+    /// folder on disk as well when `rename_folder` is set.
+    pub fn edit_instance(
+        &mut self,
+        old_name: String,
+        new_name: String,
+        instance: Instance,
+        rename_folder: bool,
+    ) -> Result<(), Error> {
+        let old_instance = self.get_instance(&old_name)?;
+        if new_name != old_name {
+            self.check_new_name(&new_name)?;
+        }
+        if old_name == "Default" && new_name != old_name {
+            return Err(Error::Instance(
+                "the Default instance can't be renamed".into(),
+            ));
+        }
+        // the lock, a saves swap and a session still carry the old name
+        if new_name != old_name {
+            self.ensure_not_running(&old_instance)?;
+        }
+
+        if rename_folder {
+            self.ensure_not_running(&old_instance)?;
+            let old_path = self.get_path(old_instance);
+            let new_path = self.get_path(instance.clone());
+            let (old_path, new_path) = (Path::new(old_path.as_str()), Path::new(new_path.as_str()));
+            if old_path != new_path && old_path.exists() {
+                if new_path.exists() {
+                    return Err(Error::Filesystem(format!(
+                        "{} already exists",
+                        new_path.display()
+                    )));
+                }
+                if let Err(e) = fs::rename(old_path, new_path) {
+                    return Err(Error::Filesystem(format!("{}: {e}", old_path.display())));
+                }
+            }
+        }
+
+        self.instances.remove(&old_name);
+        self.instances.insert(new_name.clone(), instance);
+        self.save()?;
+        if new_name != old_name {
+            backup::rename_owner(&self.backup_dir(), &old_name, &new_name)?;
+            saves::rename_player(&self.state_dir(), &old_name, &new_name)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let contents = match to_vec_pretty(&self) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::Parse(e.to_string())),
+        };
+        config::write_atomic(&self.config_path, &contents)
+    }
+
+    pub fn remove_instance(&mut self, name: String, with_folder: bool) -> Result<(), Error> {
         if name == "Default" {
-            return;
+            return Err(Error::Instance(
+                "the Default instance can't be removed".into(),
+            ));
         }
+        let instance = self.get_instance(&name)?;
         if with_folder {
-            let path = self.get_path(self.instances.get(&name).unwrap().clone());
+            self.ensure_not_running(&instance)?;
+            let path = self.get_path(instance);
             let path = Path::new(path.as_str());
             if path.exists() {
-                fs::remove_dir_all(path).expect("failed to remove dir");
+                if let Err(e) = fs::remove_dir_all(path) {
+                    return Err(Error::Filesystem(format!("{}: {e}", path.display())));
+                }
             }
         }
         self.instances.remove(&name);
-        self.save();
+        self.save()
     }
 }
 
 pub enum CurrentScreen {
+    Setup,
     Main,
     Add,
     Remove,
+    Checks,
+    DeleteDuplicate,
+    Install,
+    ConfirmInstall,
+    InboxPrompt,
+    SetInbox,
+    Clone,
+    CloneMode,
+    Edit,
+    EditRenameFolder,
+    LaunchSettings,
+    Console,
+    Stop,
+    LockConflict,
+    Logs,
+    Summary,
+    Saves,
+    Backups,
+    RestoreBackup,
     Exit,
 }
 
+pub enum Focus {
+    Instances,
+    Mods,
+}
+
 pub enum CurrentlyAdding {
     Name,
     FolderName,
     SmapiPath,
+    SavesDir,
 }
 
 pub struct App {
@@ -145,60 +650,1118 @@ pub struct App {
     pub name_input: String,
     pub folder_name_input: String,
     pub smapi_path_input: String,
+    pub saves_dir_input: String,
     pub current_instance: String,
+    pub mods: ModScan,
+    pub focus: Focus,
+    pub mod_state: TableState,
+    pub status: Option<String>,
+    /// Shown in a popup until the next key press.
+    pub error: Option<String>,
+    pub report: Report,
+    pub report_scroll: u16,
+    pub launch_pending: bool,
+    pub duplicate_index: usize,
+    pub path_input: String,
+    pub install_plan: Option<InstallPlan>,
+    pub inbox: Option<Inbox>,
+    pub inbox_queue: VecDeque<PathBuf>,
+    /// SMAPI installs found on first run.
+    pub setup_candidates: Vec<PathBuf>,
+    pub setup_index: usize,
+    /// Template being edited on the launch settings screen.
+    pub launch_draft: LaunchTemplate,
+    /// Whether the draft replaces the global template or the instance's own.
+    pub launch_global: bool,
+    /// 0 for the terminal, 1 for the wrapper, 2 for the mode.
+    pub launch_row: usize,
+    /// Last launch of every instance started since the manager was opened.
+    pub sessions: BTreeMap<String, Session>,
+    /// Process found running from the mods folder of an instance being launched.
+    pub lock_conflict: Option<Lock>,
+    /// First console line shown, or `None` to follow new output.
+    pub console_scroll: Option<usize>,
+    /// Log open in the log viewer.
+    pub log: Option<SmapiLog>,
+    /// Position of `log` among the archived logs of the current instance.
+    pub log_index: usize,
+    /// Least severe level shown in the log viewer.
+    pub log_level: LogLevel,
+    /// Only show messages from this mod.
+    pub log_source: Option<String>,
+    pub log_scroll: usize,
+    /// Session summaries of the current instance, newest first.
+    pub history: Vec<SessionSummary>,
+    pub history_index: usize,
+    /// Saves listed in the save browser.
+    pub saves: Vec<SaveGame>,
+    pub save_state: TableState,
+    /// Backups of the save selected in the save browser, newest first.
+    pub backups: Vec<Backup>,
+    pub backup_state: TableState,
 }
 
 impl App {
-    pub fn new() -> App {
-        let manager = match Manager::load_config() {
-            Ok(m) => m,
-            Err(_) => {
-                println!("Creating config file");
-                println!("Enter the path to your smapi installation: ");
-                let mut smapi_path: String = String::new();
-                io::stdin()
-                    .read_line(&mut smapi_path)
-                    .expect("Failed to read stdin");
-                let path = format!("{}/StardewModdingAPI", smapi_path.replace(['\n', '\r'], ""));
-                Manager::new(path)
-            }
-        };
-        App {
+    /// Loads the config at `config_path`, starting on the setup screen if there
+    /// is none yet. An existing config that can't be loaded is an error so it
+    /// doesn't get overwritten.
+    pub fn new(config_path: PathBuf) -> Result<App, Error> {
+        let mut status = None;
+        let (manager, screen) = if config_path.exists() {
+            let (manager, warning) = Manager::load_or_recover(&config_path)?;
+            status = warning;
+            (manager, CurrentScreen::Main)
+        } else {
+            (
+                Manager::new(String::new(), config_path),
+                CurrentScreen::Setup,
+            )
+        };
+        let mut app = App {
             manager,
-            screen: CurrentScreen::Main,
+            screen,
             adding: None,
             name_input: String::new(),
             folder_name_input: String::new(),
             smapi_path_input: String::new(),
+            saves_dir_input: String::new(),
             current_instance: "Default".into(),
+            mods: ModScan::default(),
+            focus: Focus::Instances,
+            mod_state: TableState::default(),
+            status,
+            error: None,
+            report: Report::default(),
+            report_scroll: 0,
+            launch_pending: false,
+            duplicate_index: 0,
+            path_input: String::new(),
+            install_plan: None,
+            inbox: None,
+            inbox_queue: VecDeque::new(),
+            setup_candidates: Vec::new(),
+            setup_index: 0,
+            launch_draft: LaunchTemplate::default(),
+            launch_global: false,
+            launch_row: 0,
+            sessions: BTreeMap::new(),
+            lock_conflict: None,
+            console_scroll: None,
+            log: None,
+            log_index: 0,
+            log_level: LogLevel::Trace,
+            log_source: None,
+            log_scroll: 0,
+            history: Vec::new(),
+            history_index: 0,
+            saves: Vec::new(),
+            save_state: TableState::default(),
+            backups: Vec::new(),
+            backup_state: TableState::default(),
+        };
+        if let CurrentScreen::Setup = app.screen {
+            app.setup_candidates = detect::find_smapi_installs();
+            return Ok(app);
         }
+        match saves::recover(&app.manager.state_dir()) {
+            Ok(Some(instance)) => {
+                app.status = Some(format!(
+                    "Restored the shared saves swapped out for {instance}"
+                ))
+            }
+            Ok(None) => (),
+            Err(e) => app.show_error("Failed to restore the shared saves".into(), e),
+        }
+        app.refresh_mods();
+        app.watch_inbox();
+        Ok(app)
     }
 
-    pub fn save_instance(&mut self) {
-        if self.manager.instances.contains_key(&self.name_input) {
+    /// Uses the typed path if there is one, otherwise the selected candidate,
+    /// and writes the first config once the path checks out.
+    pub fn finish_setup(&mut self) {
+        let smapi_path = if self.path_input.trim().is_empty() {
+            match self.setup_candidates.get(self.setup_index) {
+                Some(candidate) => candidate.clone(),
+                None => return,
+            }
+        } else {
+            match detect::validate_smapi_path(&self.path_input) {
+                Ok(path) => path,
+                Err(e) => {
+                    self.show_error("Invalid SMAPI path".into(), e);
+                    return;
+                }
+            }
+        };
+        self.manager.smapi_path = smapi_path.to_string_lossy().into_owned();
+        if let Err(e) = self.manager.save() {
+            self.show_error("Failed to save config".into(), e);
             return;
         }
+        self.path_input = String::new();
+        self.status = Some(format!("Using {}", self.manager.smapi_path));
+        self.screen = CurrentScreen::Main;
+        self.refresh_mods();
+        self.watch_inbox();
+    }
 
-        if self.smapi_path_input.is_empty() {
-            self.manager.add_instance(
-                Instance::new(self.folder_name_input.clone(), None),
-                self.name_input.clone(),
-            );
+    pub fn select_next_candidate(&mut self) {
+        if !self.setup_candidates.is_empty() {
+            self.setup_index = (self.setup_index + 1) % self.setup_candidates.len();
+        }
+    }
+
+    pub fn select_prev_candidate(&mut self) {
+        if !self.setup_candidates.is_empty() {
+            self.setup_index = match self.setup_index {
+                0 => self.setup_candidates.len() - 1,
+                index => index - 1,
+            };
+        }
+    }
+
+    pub fn refresh_mods(&mut self) {
+        self.mods = match self.manager.instances.get(&self.current_instance) {
+            Some(instance) => self.manager.scan_mods(instance.clone()),
+            None => ModScan::default(),
+        };
+        if self.mod_count() == 0 {
+            self.mod_state.select(None);
         } else {
-            self.manager.add_instance(
-                Instance::new(
-                    self.folder_name_input.clone(),
-                    Some(self.smapi_path_input.clone()),
+            self.mod_state.select(Some(0));
+        }
+    }
+
+    /// Number of rows in the mod pane, including manifests that failed to load.
+    pub fn mod_count(&self) -> usize {
+        self.mods.mods.len() + self.mods.errors.len()
+    }
+
+    pub fn toggle_focus(&mut self) {
+        match self.focus {
+            Focus::Instances => self.focus = Focus::Mods,
+            Focus::Mods => self.focus = Focus::Instances,
+        }
+    }
+
+    pub fn show_error(&mut self, context: String, error: Error) {
+        self.error = Some(format!("{context}: {error}"));
+    }
+
+    pub fn open_current(&mut self) {
+        let result = self
+            .manager
+            .get_instance(&self.current_instance)
+            .and_then(|instance| self.manager.open(instance));
+        if let Err(e) = result {
+            self.show_error("Failed to open folder".into(), e);
+        }
+    }
+
+    pub fn remove_current(&mut self, with_folder: bool) {
+        let to_delete = self.current_instance.clone();
+        match self.manager.remove_instance(to_delete.clone(), with_folder) {
+            Ok(_) => {
+                self.select_next();
+                self.status = Some(format!("Removed {to_delete}"));
+            }
+            Err(e) => self.show_error(format!("Failed to remove {to_delete}"), e),
+        }
+        self.screen = CurrentScreen::Main;
+    }
+
+    pub fn run_checks(&mut self) {
+        self.refresh_mods();
+        self.report = Report::check(&self.mods);
+        self.report_scroll = 0;
+        if self.duplicate_index >= self.report.duplicates.len() {
+            self.duplicate_index = 0;
+        }
+    }
+
+    pub fn select_next_duplicate(&mut self) {
+        if !self.report.duplicates.is_empty() {
+            self.duplicate_index = (self.duplicate_index + 1) % self.report.duplicates.len();
+        }
+    }
+
+    /// Disables or deletes every copy of the selected duplicate except the newest.
+    pub fn resolve_duplicate(&mut self, delete: bool) {
+        let duplicate = match self.report.duplicates.get(self.duplicate_index) {
+            Some(d) => d.clone(),
+            None => return,
+        };
+        if let Err(e) = self.ensure_not_running() {
+            self.show_error(format!("Failed to resolve {}", duplicate.unique_id), e);
+            return;
+        }
+        for older in duplicate.older_copies() {
+            let result = if delete {
+                older.delete()
+            } else {
+                older.set_enabled(false).map(|_| ())
+            };
+            if let Err(e) = result {
+                self.show_error(format!("Failed to resolve {}", duplicate.unique_id), e);
+                self.run_checks();
+                return;
+            }
+        }
+        let action = if delete { "Deleted" } else { "Disabled" };
+        self.status = Some(format!(
+            "{action} older copies of {}, keeping {}",
+            duplicate.unique_id,
+            duplicate.newest().manifest.version
+        ));
+        self.run_checks();
+    }
+
+    /// Launches the current instance, stopping on the report screen first when
+    /// required dependencies are unresolved unless `force` is set.
+    pub fn start_instance(&mut self, force: bool) {
+        if !force {
+            self.run_checks();
+            if self.report.has_blocking() {
+                self.launch_pending = true;
+                self.screen = CurrentScreen::Checks;
+                return;
+            }
+        }
+        self.launch_pending = false;
+        self.screen = CurrentScreen::Main;
+        match self.find_lock(&self.current_instance) {
+            Ok(Some(lock)) => {
+                self.lock_conflict = Some(lock);
+                self.screen = CurrentScreen::LockConflict;
+            }
+            Ok(None) => self.launch_current(),
+            Err(e) => self.show_error(format!("Failed to start {}", self.current_instance), e),
+        }
+    }
+
+    /// A running session using the same mods folder as `name`, or else a lock
+    /// left by SMAPI started from somewhere else.
+    fn find_lock(&self, name: &str) -> Result<Option<Lock>, Error> {
+        let mods = self.manager.mods_path(self.manager.get_instance(name)?);
+        for session in self.sessions.values() {
+            if session.is_running() && session.mods == mods {
+                return Ok(Some(Lock {
+                    pid: session.pid(),
+                    instance: session.instance.clone(),
+                }));
+            }
+        }
+        self.manager.running_lock(name)
+    }
+
+    /// Fails while a game runs from the mods folder of the current instance,
+    /// whose mods must not change then.
+    fn ensure_not_running(&self) -> Result<(), Error> {
+        match self.find_lock(&self.current_instance)? {
+            Some(lock) => Err(Error::Instance(format!(
+                "{} (pid {}) is running from this mods folder, stop it first",
+                lock.instance, lock.pid
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Starts the current instance without any more checks.
+    pub fn launch_current(&mut self) {
+        self.lock_conflict = None;
+        self.screen = CurrentScreen::Main;
+        let name = self.current_instance.clone();
+        // sessions are tracked by instance, so a second one would be lost
+        if let Some(session) = self.sessions.get(&name).filter(|s| s.is_running()) {
+            let e = Error::Instance(format!(
+                "{name} is already running (pid {}), stop it first",
+                session.pid()
+            ));
+            self.show_error(format!("Failed to start {name}"), e);
+            return;
+        }
+        let own_saves = match self.manager.get_instance(&name) {
+            Ok(instance) => instance.saves_dir.is_some(),
+            Err(_) => false,
+        };
+        // sessions don't always leave a lock, like in a missing mods folder
+        let running = self.sessions.values().find(|session| session.is_running());
+        if let (true, Some(session)) = (own_saves, running) {
+            let e = Error::Instance(format!(
+                "{} (pid {}) is still running, launch again after it exits",
+                session.instance,
+                session.pid()
+            ));
+            self.show_error(format!("Failed to start {name}"), e);
+            return;
+        }
+        match self.manager.start(&name) {
+            Ok(session) => {
+                if session.headless {
+                    self.console_scroll = None;
+                    self.screen = CurrentScreen::Console;
+                }
+                self.status = Some(format!("Started {name} (pid {})", session.pid()));
+                self.sessions.insert(name, session);
+            }
+            Err(e) => self.show_error(format!("Failed to start {name}"), e),
+        }
+    }
+
+    pub fn current_session(&self) -> Option<&Session> {
+        self.sessions.get(&self.current_instance)
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        match self.sessions.get(name) {
+            Some(session) => session.is_running(),
+            None => false,
+        }
+    }
+
+    pub fn stop_current(&mut self) {
+        self.screen = CurrentScreen::Main;
+        if let Some(session) = self.sessions.get_mut(&self.current_instance) {
+            match session.stop() {
+                Ok(_) => self.status = Some(format!("Stopping {}", session.instance)),
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+    }
+
+    /// Stops whatever holds the lock the last launch ran into.
+    pub fn stop_conflicting(&mut self) {
+        self.screen = CurrentScreen::Main;
+        let lock = match self.lock_conflict.take() {
+            Some(lock) => lock,
+            None => return,
+        };
+        let own = self
+            .sessions
+            .values_mut()
+            .find(|session| session.is_running() && session.pid() == lock.pid);
+        let result = match own {
+            Some(session) => session.stop(),
+            None => session::stop_process_group(lock.pid),
+        };
+        match result {
+            Ok(_) => self.status = Some(format!("Stopping {}", lock.instance)),
+            Err(e) => self.show_error(format!("Failed to stop {}", lock.instance), e),
+        }
+    }
+
+    /// Headless sessions can't outlive the manager, as nothing would read their
+    /// output. Waits for them to exit, killing those that take too long, so
+    /// their locks and saves swaps are undone before the manager quits.
+    pub fn stop_headless(&mut self) -> Result<(), Error> {
+        let headless = |session: &Session| session.headless && session.is_running();
+        for session in self.sessions.values_mut() {
+            if headless(session) {
+                let _ = session.stop();
+            }
+        }
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while self.sessions.values().any(headless) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(100));
+            self.poll_sessions();
+        }
+        for session in self.sessions.values_mut() {
+            if headless(session) {
+                let _ = session.kill();
+            }
+        }
+        self.poll_sessions();
+        saves::recover(&self.manager.state_dir())?;
+        Ok(())
+    }
+
+    /// Moves the console view by `lines`, going back to following the output
+    /// once the end is reached.
+    pub fn scroll_console(&mut self, lines: isize, height: usize) {
+        let count = match self.current_session() {
+            Some(session) => session.console.len(),
+            None => 0,
+        };
+        let last = count.saturating_sub(height);
+        let current = self.console_scroll.unwrap_or(last);
+        let next = current.saturating_add_signed(lines).min(last);
+        self.console_scroll = if next == last { None } else { Some(next) };
+    }
+
+    /// Notices sessions that ended, archives their logs and shows the log of
+    /// the selected instance.
+    fn poll_sessions(&mut self) {
+        let mut finished = false;
+        let stopped: Vec<String> = self
+            .sessions
+            .iter_mut()
+            .filter_map(|(name, session)| session.poll().then(|| name.clone()))
+            .collect();
+        for name in stopped {
+            let session = match self.sessions.get(&name) {
+                Some(session) => session,
+                None => continue,
+            };
+            Lock::release(&session.mods, session.pid());
+            let restored = saves::release(&self.manager.state_dir(), session.pid());
+            let overlapped = self.sessions.iter().any(|(other_name, other)| {
+                *other_name != name
+                    && other.ran_after(session.started)
+                    && self.plays_on_same_saves(&name, other_name)
+            });
+            let _ = self
+                .manager
+                .record_saves(&session.instance, session.started, overlapped);
+            let (ended, exit_code) = match &session.status {
+                SessionStatus::Exited(status) => (
+                    format!("{} exited with {status}", session.instance),
+                    status.code(),
                 ),
-                self.name_input.clone(),
+                SessionStatus::Lost(e) => {
+                    (format!("Lost track of {}: {e}", session.instance), None)
+                }
+                SessionStatus::Running => continue,
+            };
+            // SMAPI-latest.txt belongs to whichever SMAPI started last
+            let log_replaced = self
+                .sessions
+                .values()
+                .any(|other| other.started > session.started);
+            let summary = summary::finish_session(
+                &session.instance,
+                &session.mods,
+                session.started,
+                exit_code,
+                log_replaced,
             );
+            let details = match summary {
+                Ok(summary) => {
+                    if session.instance == self.current_instance {
+                        finished = true;
+                    }
+                    match (summary.log.is_some(), summary.error_count()) {
+                        (false, _) if log_replaced => {
+                            ", its log was replaced by a later launch".to_string()
+                        }
+                        (false, _) => ", SMAPI wrote no log".to_string(),
+                        (true, 0) => String::new(),
+                        (true, errors) => format!(", {errors} error(s) logged"),
+                    }
+                }
+                Err(e) => format!(", failed to record the session: {e}"),
+            };
+            let details = match restored {
+                Ok(_) => details,
+                Err(e) => format!("{details}, failed to restore the shared saves: {e}"),
+            };
+            self.status = Some(format!("{ended}{details}"));
+        }
+        if finished {
+            if let CurrentScreen::Main | CurrentScreen::Console = self.screen {
+                self.open_history();
+            }
+        }
+    }
+
+    /// Whether instances `a` and `b` play on the same saves folder.
+    fn plays_on_same_saves(&self, a: &str, b: &str) -> bool {
+        match (self.manager.get_instance(a), self.manager.get_instance(b)) {
+            (Ok(a), Ok(b)) => self.manager.saves_root(&a) == self.manager.saves_root(&b),
+            _ => false,
+        }
+    }
+
+    /// Shows the summary of the last session of the current instance.
+    pub fn open_history(&mut self) {
+        self.history = match self.manager.get_instance(&self.current_instance) {
+            Ok(instance) => summary::history(&self.manager.mods_path(instance)),
+            Err(_) => Vec::new(),
+        };
+        self.history.reverse();
+        self.history_index = 0;
+        if self.history.is_empty() {
+            self.status = Some(format!(
+                "{} has no recorded sessions yet",
+                self.current_instance
+            ));
+            return;
+        }
+        self.screen = CurrentScreen::Summary;
+    }
+
+    /// Switches to an older (`1`) or newer (`-1`) session.
+    pub fn step_history(&mut self, step: isize) {
+        let index = self.history_index.saturating_add_signed(step);
+        if index < self.history.len() {
+            self.history_index = index;
+        }
+    }
+
+    /// Opens the log of the session shown in the summary.
+    pub fn open_summary_log(&mut self) {
+        let log = match self.history.get(self.history_index) {
+            Some(summary) => summary.log.clone(),
+            None => return,
+        };
+        match log {
+            Some(path) => self.show_log(&path),
+            None => self.status = Some("SMAPI wrote no log for this session".into()),
+        }
+    }
+
+    pub fn open_saves(&mut self) {
+        self.saves = self.manager.list_saves();
+        if self.saves.is_empty() {
+            self.status = Some("No saves found".into());
+            return;
+        }
+        self.save_state.select(Some(0));
+        self.screen = CurrentScreen::Saves;
+    }
+
+    pub fn select_next_save(&mut self) {
+        if self.saves.is_empty() {
+            return;
+        }
+        let next = match self.save_state.selected() {
+            Some(index) => (index + 1) % self.saves.len(),
+            None => 0,
         };
+        self.save_state.select(Some(next));
+    }
 
-        self.manager.save();
+    pub fn select_prev_save(&mut self) {
+        if self.saves.is_empty() {
+            return;
+        }
+        let prev = match self.save_state.selected() {
+            Some(0) | None => self.saves.len() - 1,
+            Some(index) => index - 1,
+        };
+        self.save_state.select(Some(prev));
+    }
+
+    /// Selects the instance that last played the selected save.
+    pub fn select_save_instance(&mut self) {
+        let save = match self.save_state.selected().and_then(|i| self.saves.get(i)) {
+            Some(save) => save,
+            None => return,
+        };
+        let instance = match &save.played_by {
+            Some(played_by) => played_by.instance.clone(),
+            None => {
+                self.status = Some(format!("No instance has played {} yet", save.farm_name));
+                return;
+            }
+        };
+        if !self.manager.instances.contains_key(&instance) {
+            self.status = Some(format!("{instance} no longer exists"));
+            return;
+        }
+        self.current_instance = instance;
+        self.screen = CurrentScreen::Main;
+        self.refresh_mods();
+    }
+
+    /// Lists the backups of the save selected in the save browser.
+    pub fn open_backups(&mut self) {
+        let save = match self.save_state.selected().and_then(|i| self.saves.get(i)) {
+            Some(save) => save,
+            None => return,
+        };
+        let owner = save.owner.as_deref().unwrap_or(backup::SHARED_OWNER);
+        self.backups = backup::backups(&self.manager.backup_dir(), owner, &save.folder);
+        if self.backups.is_empty() {
+            self.status = Some(format!("{} has no backups yet", save.farm_name));
+            return;
+        }
+        self.backup_state.select(Some(0));
+        self.screen = CurrentScreen::Backups;
+    }
+
+    pub fn select_next_backup(&mut self) {
+        if self.backups.is_empty() {
+            return;
+        }
+        let next = match self.backup_state.selected() {
+            Some(index) => (index + 1) % self.backups.len(),
+            None => 0,
+        };
+        self.backup_state.select(Some(next));
+    }
+
+    pub fn select_prev_backup(&mut self) {
+        if self.backups.is_empty() {
+            return;
+        }
+        let prev = match self.backup_state.selected() {
+            Some(0) | None => self.backups.len() - 1,
+            Some(index) => index - 1,
+        };
+        self.backup_state.select(Some(prev));
+    }
+
+    pub fn selected_backup(&self) -> Option<&Backup> {
+        self.backup_state
+            .selected()
+            .and_then(|index| self.backups.get(index))
+    }
+
+    /// Rolls the save back to the selected backup. Refused while anything
+    /// runs, as the game only writes its saves when a day ends.
+    pub fn restore_backup(&mut self) {
+        let backup = match self.selected_backup() {
+            Some(backup) => backup.clone(),
+            None => return,
+        };
+        if self.sessions.values().any(|session| session.is_running()) {
+            self.status = Some("Stop the running instances before restoring a save".into());
+            self.screen = CurrentScreen::Backups;
+            return;
+        }
+        match self.manager.restore_save(&backup) {
+            Ok(_) => {
+                self.status = Some(format!(
+                    "Restored {} from {}",
+                    backup.save,
+                    backup.time.format("%Y-%m-%d %H:%M:%S")
+                ));
+                self.saves = self.manager.list_saves();
+                self.screen = CurrentScreen::Saves;
+            }
+            Err(e) => {
+                self.show_error(format!("Failed to restore {}", backup.save), e);
+                self.screen = CurrentScreen::Backups;
+            }
+        }
+    }
+
+    /// Opens the newest archived log of the current instance, or the log of
+    /// the last launch from anywhere if there is none yet.
+    pub fn open_logs(&mut self) {
+        let mods = match self.manager.get_instance(&self.current_instance) {
+            Ok(instance) => self.manager.mods_path(instance),
+            Err(e) => {
+                self.show_error("Failed to open logs".into(), e);
+                return;
+            }
+        };
+        match logs::archived_logs(&mods).first() {
+            Some(newest) => self.show_log(&newest.clone()),
+            None => self.show_log(&logs::latest_log_path()),
+        }
+    }
+
+    fn show_log(&mut self, path: &Path) {
+        match SmapiLog::load(path) {
+            Ok(log) => {
+                self.log = Some(log);
+                self.log_source = None;
+                self.log_scroll = 0;
+                self.log_index = self
+                    .current_logs()
+                    .iter()
+                    .position(|archived| archived == path)
+                    .unwrap_or(0);
+                self.screen = CurrentScreen::Logs;
+            }
+            Err(e) => self.show_error("Failed to open log".into(), e),
+        }
+    }
+
+    fn current_logs(&self) -> Vec<PathBuf> {
+        match self.manager.get_instance(&self.current_instance) {
+            Ok(instance) => logs::archived_logs(&self.manager.mods_path(instance)),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Switches to an older (`1`) or newer (`-1`) archived log.
+    pub fn step_log(&mut self, step: isize) {
+        let archived = self.current_logs();
+        let index = self.log_index.saturating_add_signed(step);
+        if let Some(path) = archived.get(index) {
+            self.show_log(&path.clone());
+        }
+    }
+
+    pub fn cycle_log_level(&mut self) {
+        let index = LogLevel::ALL
+            .iter()
+            .position(|level| *level == self.log_level)
+            .unwrap_or(0);
+        self.log_level = LogLevel::ALL[(index + 1) % LogLevel::ALL.len()];
+        self.log_scroll = 0;
+    }
+
+    /// Goes through the mods in the log, then back to showing all of them.
+    pub fn cycle_log_source(&mut self) {
+        let sources = match &self.log {
+            Some(log) => log.sources(),
+            None => return,
+        };
+        let next = match &self.log_source {
+            Some(current) => match sources.iter().position(|source| source == current) {
+                Some(index) => sources.get(index + 1).cloned(),
+                None => None,
+            },
+            None => sources.first().cloned(),
+        };
+        self.log_source = next;
+        self.log_scroll = 0;
+    }
+
+    /// Number of lines the filtered log takes up in the viewer.
+    pub fn log_line_count(&self) -> usize {
+        match &self.log {
+            Some(log) => log
+                .filtered(self.log_level, self.log_source.as_deref())
+                .map(|entry| entry.message.lines().count().max(1))
+                .sum(),
+            None => 0,
+        }
+    }
+
+    pub fn scroll_log(&mut self, lines: isize, height: usize) {
+        let last = self.log_line_count().saturating_sub(height);
+        self.log_scroll = self.log_scroll.saturating_add_signed(lines).min(last);
+    }
+
+    pub fn start_launch_settings(&mut self) {
+        let has_override = match self.manager.instances.get(&self.current_instance) {
+            Some(instance) => instance.launch.is_some(),
+            None => return,
+        };
+        self.launch_global = !has_override;
+        self.load_launch_draft();
+        self.launch_row = 0;
+        self.screen = CurrentScreen::LaunchSettings;
+    }
+
+    /// Fills the draft from the template currently used in the selected scope.
+    fn load_launch_draft(&mut self) {
+        self.launch_draft = match self.manager.instances.get(&self.current_instance) {
+            Some(instance) if !self.launch_global => self.manager.launch_template(instance),
+            _ => self.manager.launch.clone(),
+        };
+    }
+
+    pub fn toggle_launch_scope(&mut self) {
+        self.launch_global = !self.launch_global;
+        self.load_launch_draft();
+    }
+
+    /// Switches the selected row to the next or previous preset. A custom
+    /// template from the config starts over at the first preset.
+    pub fn cycle_launch_preset(&mut self, forward: bool) {
+        let step = |index: Option<usize>, len: usize| match index {
+            Some(index) if forward => (index + 1) % len,
+            Some(0) => len - 1,
+            Some(index) => index - 1,
+            None => 0,
+        };
+        match self.launch_row {
+            0 => {
+                let index = step(self.launch_draft.terminal_preset(), TERMINAL_PRESETS.len());
+                self.launch_draft.terminal = TERMINAL_PRESETS[index].1.into();
+            }
+            1 => {
+                let index = step(self.launch_draft.wrapper_preset(), WRAPPER_PRESETS.len());
+                self.launch_draft.command = WRAPPER_PRESETS[index].1.into();
+            }
+            _ => {
+                self.launch_draft.mode = match self.launch_draft.mode {
+                    LaunchMode::Terminal => LaunchMode::Headless,
+                    LaunchMode::Headless => LaunchMode::Terminal,
+                }
+            }
+        }
+    }
+
+    pub fn save_launch_settings(&mut self) {
+        let draft = self.launch_draft.clone();
+        if self.launch_global {
+            self.manager.launch = draft;
+        } else if let Some(instance) = self.manager.instances.get_mut(&self.current_instance) {
+            instance.launch = Some(draft);
+        }
+        self.finish_launch_settings("Saved launch settings");
+    }
+
+    /// Makes the current instance use the global template again.
+    pub fn reset_launch_override(&mut self) {
+        if let Some(instance) = self.manager.instances.get_mut(&self.current_instance) {
+            instance.launch = None;
+        }
+        self.finish_launch_settings("Using the global launch settings");
+    }
+
+    fn finish_launch_settings(&mut self, status: &str) {
+        match self.manager.save() {
+            Ok(_) => self.status = Some(status.to_string()),
+            Err(e) => self.show_error("Failed to save config".into(), e),
+        }
+        self.screen = CurrentScreen::Main;
+    }
+
+    /// Reads `archive` and installs it right away unless it would upgrade an
+    /// installed mod, which needs confirmation first.
+    pub fn prepare_install(&mut self, archive: PathBuf) {
+        let instance = match self.manager.instances.get(&self.current_instance) {
+            Some(instance) => instance.clone(),
+            None => return,
+        };
+        match self.manager.plan_install(instance, &archive) {
+            Ok(plan) => {
+                let upgrades = plan.has_upgrades();
+                self.install_plan = Some(plan);
+                if upgrades {
+                    self.screen = CurrentScreen::ConfirmInstall;
+                } else {
+                    self.finish_install();
+                }
+            }
+            Err(e) => {
+                self.screen = CurrentScreen::Main;
+                self.show_error("Failed to install".into(), e);
+            }
+        }
+    }
+
+    pub fn watch_inbox(&mut self) {
+        self.inbox = self
+            .manager
+            .inbox
+            .as_ref()
+            .map(|path| Inbox::new(expand_home(path)));
+        self.inbox_queue.clear();
+    }
+
+    pub fn set_inbox(&mut self) {
+        let path = self.path_input.trim().to_string();
+        self.manager.inbox = if path.is_empty() { None } else { Some(path) };
+        if let Err(e) = self.manager.save() {
+            self.show_error("Failed to save config".into(), e);
+        }
+        self.path_input = String::new();
+        self.watch_inbox();
+        self.screen = CurrentScreen::Main;
+    }
+
+    /// Called on every pass of the event loop.
+    pub fn tick(&mut self) {
+        self.poll_sessions();
+        if let Some(inbox) = &mut self.inbox {
+            self.inbox_queue.extend(inbox.poll());
+        }
+        if let CurrentScreen::Main = self.screen {
+            if !self.inbox_queue.is_empty() {
+                self.screen = CurrentScreen::InboxPrompt;
+            }
+        }
+    }
+
+    pub fn answer_inbox(&mut self, install: bool) {
+        let archive = match self.inbox_queue.pop_front() {
+            Some(archive) => archive,
+            None => return,
+        };
+        self.screen = CurrentScreen::Main;
+        if install {
+            self.prepare_install(archive);
+        }
+    }
+
+    pub fn finish_install(&mut self) {
+        if let Some(plan) = self.install_plan.take() {
+            // the game may have been started while the upgrade was confirmed
+            if let Err(e) = self.ensure_not_running() {
+                self.show_error("Failed to install".into(), e);
+                self.screen = CurrentScreen::Main;
+                return;
+            }
+            match plan.install() {
+                Ok(paths) => self.status = Some(format!("Installed {} mod(s)", paths.len())),
+                Err(e) => self.show_error("Failed to install".into(), e),
+            }
+        }
+        self.screen = CurrentScreen::Main;
+        self.refresh_mods();
+    }
+
+    pub fn toggle_selected_mod(&mut self) {
+        let installed = match self.mod_state.selected() {
+            Some(index) => match self.mods.mods.get(index) {
+                Some(installed) => installed.clone(),
+                None => return,
+            },
+            None => return,
+        };
+        if let Err(e) = self.ensure_not_running() {
+            self.show_error(format!("Failed to toggle {}", installed.manifest.name), e);
+            return;
+        }
+        let selected = self.mod_state.selected();
+        match installed.set_enabled(!installed.enabled) {
+            Ok(_) if installed.enabled => {
+                self.status = Some(format!("Disabled {}", installed.manifest.name))
+            }
+            Ok(_) => self.status = Some(format!("Enabled {}", installed.manifest.name)),
+            Err(e) => self.show_error(format!("Failed to toggle {}", installed.manifest.name), e),
+        }
+        self.refresh_mods();
+        self.mod_state.select(selected);
+    }
+
+    pub fn select_next_mod(&mut self) {
+        let count = self.mod_count();
+        if count == 0 {
+            return;
+        }
+        let next = match self.mod_state.selected() {
+            Some(index) => (index + 1) % count,
+            None => 0,
+        };
+        self.mod_state.select(Some(next));
+    }
+
+    pub fn select_prev_mod(&mut self) {
+        let count = self.mod_count();
+        if count == 0 {
+            return;
+        }
+        let prev = match self.mod_state.selected() {
+            Some(0) | None => count - 1,
+            Some(index) => index - 1,
+        };
+        self.mod_state.select(Some(prev));
+    }
+
+    pub fn save_instance(&mut self) {
+        if self.name_is_taken() {
+            return;
+        }
+
+        let mut instance = if self.smapi_path_input.is_empty() {
+            Instance::new(self.folder_name_input.clone(), None)
+        } else {
+            Instance::new(
+                self.folder_name_input.clone(),
+                Some(self.smapi_path_input.clone()),
+            )
+        };
+        if !self.saves_dir_input.is_empty() {
+            instance.saves_dir = Some(self.saves_dir_input.clone());
+        }
+        self.manager.add_instance(instance, self.name_input.clone());
+
+        if let Err(e) = self.manager.save() {
+            self.show_error("Failed to save config".into(), e);
+        }
 
         self.name_input = String::new();
         self.folder_name_input = String::new();
         self.smapi_path_input = String::new();
+        self.saves_dir_input = String::new();
+    }
+
+    /// Whether `name_input` collides with an instance other than the one being
+    /// edited, or is reserved.
+    pub fn name_is_taken(&self) -> bool {
+        if let CurrentScreen::Edit = self.screen {
+            if self.name_input == self.current_instance {
+                return false;
+            }
+        }
+        self.manager.check_new_name(&self.name_input).is_err()
+    }
+
+    pub fn start_edit(&mut self) {
+        let instance = match self.manager.instances.get(&self.current_instance) {
+            Some(instance) => instance.clone(),
+            None => return,
+        };
+        self.name_input = self.current_instance.clone();
+        self.folder_name_input = instance.folder_name;
+        self.smapi_path_input = instance.smapi_path.unwrap_or_default();
+        self.saves_dir_input = instance.saves_dir.unwrap_or_default();
+        self.adding = Some(CurrentlyAdding::Name);
+        self.screen = CurrentScreen::Edit;
+    }
+
+    /// Asks whether to move the folder on disk when the folder name changed.
+    pub fn submit_edit(&mut self) {
+        let instance = match self.manager.instances.get(&self.current_instance) {
+            Some(instance) => instance.clone(),
+            None => return,
+        };
+        let old_path = self.manager.get_path(instance.clone());
+        if instance.folder_name != self.folder_name_input && Path::new(old_path.as_str()).exists() {
+            self.screen = CurrentScreen::EditRenameFolder;
+        } else {
+            self.finish_edit(false);
+        }
+    }
+
+    pub fn finish_edit(&mut self, rename_folder: bool) {
+        let smapi_path = if self.smapi_path_input.is_empty() {
+            None
+        } else {
+            Some(self.smapi_path_input.clone())
+        };
+        let mut instance = Instance::new(self.folder_name_input.clone(), smapi_path);
+        if !self.saves_dir_input.is_empty() {
+            instance.saves_dir = Some(self.saves_dir_input.clone());
+        }
+        if let Some(old) = self.manager.instances.get(&self.current_instance) {
+            instance.launch = old.launch.clone();
+        }
+        let result = if self.name_input != self.current_instance
+            && self.is_running(&self.current_instance)
+        {
+            Err(Error::Instance(format!(
+                "{} is running, stop it before renaming it",
+                self.current_instance
+            )))
+        } else {
+            self.manager.edit_instance(
+                self.current_instance.clone(),
+                self.name_input.clone(),
+                instance,
+                rename_folder,
+            )
+        };
+        match result {
+            Ok(_) => {
+                self.status = Some(format!("Saved {}", self.name_input));
+                self.current_instance = self.name_input.clone();
+            }
+            Err(e) => self.show_error(format!("Failed to edit {}", self.current_instance), e),
+        }
+        self.name_input = String::new();
+        self.folder_name_input = String::new();
+        self.smapi_path_input = String::new();
+        self.saves_dir_input = String::new();
+        self.adding = None;
+        self.screen = CurrentScreen::Main;
+        self.refresh_mods();
+    }
+
+    pub fn finish_clone(&mut self, result: Result<(), Error>) {
+        match result {
+            Ok(_) => {
+                self.status = Some(format!(
+                    "Cloned {} as {}",
+                    self.current_instance, self.name_input
+                ))
+            }
+            Err(e) => self.show_error(format!("Failed to clone {}", self.current_instance), e),
+        }
+        self.name_input = String::new();
+        self.folder_name_input = String::new();
+        self.adding = None;
+        self.screen = CurrentScreen::Main;
     }
 
     pub fn toggle_adding(&mut self) {
@@ -206,7 +1769,8 @@ impl App {
             match adding_mode {
                 CurrentlyAdding::Name => self.adding = Some(CurrentlyAdding::FolderName),
                 CurrentlyAdding::FolderName => self.adding = Some(CurrentlyAdding::SmapiPath),
-                CurrentlyAdding::SmapiPath => self.adding = Some(CurrentlyAdding::Name),
+                CurrentlyAdding::SmapiPath => self.adding = Some(CurrentlyAdding::SavesDir),
+                CurrentlyAdding::SavesDir => self.adding = Some(CurrentlyAdding::Name),
             }
         } else {
             self.adding = Some(CurrentlyAdding::Name);
@@ -220,6 +1784,7 @@ impl App {
             None => keys.first().cloned(),
         };
         self.current_instance = next_key.unwrap();
+        self.refresh_mods();
     }
 
     pub fn select_prev(&mut self) {
@@ -235,5 +1800,17 @@ impl App {
             None => keys.first().cloned(),
         };
         self.current_instance = prev_key.unwrap();
+        self.refresh_mods();
+    }
+}
+
+/// Expands `~` and a leading `~/` to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    // `~user` is left alone, only the current user's home is known
+    if path == "~" || path.starts_with("~/") {
+        if let Ok(home) = env::var("HOME") {
+            return PathBuf::from(format!("{home}{}", &path[1..]));
+        }
     }
+    PathBuf::from(path)
 }
@@ -0,0 +1,442 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeSet,
+    fs::{self, File},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use chrono::{Datelike, Local, NaiveDateTime};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder};
+
+use crate::error::Error;
+
+/// Owner of the backups of the game's own saves, so no instance can have this name.
+pub const SHARED_OWNER: &str = "shared";
+
+/// Backups are named after the time they were taken. Older ones were named
+/// without milliseconds, which this reads as well.
+const STAMP_FORMAT: &str = "%Y-%m-%d_%H-%M-%S%.3f";
+const EXTENSION: &str = ".tar.gz";
+
+/// Which backups of a save are kept. A backup stays if any rule keeps it, and
+/// the newest one always stays.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupPolicy {
+    /// Back up the saves of an instance before launching it.
+    pub enabled: bool,
+    /// Keeps this many of the newest backups.
+    pub keep_last: usize,
+    /// Keeps the newest backup of each of this many days.
+    pub keep_daily: usize,
+    /// Keeps the newest backup of each of this many weeks.
+    pub keep_weekly: usize,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> BackupPolicy {
+        BackupPolicy {
+            enabled: true,
+            keep_last: 5,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+/// A compressed copy of one save folder.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    /// `shared` for the game's own saves, otherwise the instance whose saves
+    /// folder holds the save.
+    pub owner: String,
+    /// Name of the save folder.
+    pub save: String,
+    pub path: PathBuf,
+    pub time: NaiveDateTime,
+    pub size: u64,
+}
+
+impl Backup {
+    fn read(owner: &str, save: &str, path: PathBuf) -> Option<Backup> {
+        let stamp = path.file_name()?.to_str()?.strip_suffix(EXTENSION)?;
+        let time = NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT).ok()?;
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+        Some(Backup {
+            owner: owner.to_string(),
+            save: save.to_string(),
+            path,
+            time,
+            size,
+        })
+    }
+}
+
+fn is_save(path: &Path) -> bool {
+    path.join("SaveGameInfo").is_file()
+}
+
+fn dir_names(path: &Path) -> Vec<String> {
+    match fs::read_dir(path) {
+        Ok(entries) => entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Backups of the save folder `save` of `owner` in the backup folder `dir`,
+/// which holds them as `<owner>/<save>/<time>.tar.gz`. Newest first.
+pub fn backups(dir: &Path, owner: &str, save: &str) -> Vec<Backup> {
+    let entries = match fs::read_dir(dir.join(owner).join(save)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut backups: Vec<Backup> = entries
+        .flatten()
+        .filter_map(|entry| Backup::read(owner, save, entry.path()))
+        .collect();
+    backups.sort_by_key(|backup| Reverse(backup.time));
+    backups
+}
+
+/// Every backup, grouped by owner and save.
+pub fn all_backups(dir: &Path) -> Vec<Backup> {
+    let mut owners = dir_names(dir);
+    owners.sort();
+    let mut all = Vec::new();
+    for owner in owners {
+        let mut saves = dir_names(&dir.join(&owner));
+        saves.sort();
+        for save in saves {
+            all.extend(backups(dir, &owner, &save));
+        }
+    }
+    all
+}
+
+/// Moves the backups of `old_owner` in `dir` over to `new_owner`, keeping
+/// any that `new_owner` already has.
+pub fn rename_owner(dir: &Path, old_owner: &str, new_owner: &str) -> Result<(), Error> {
+    let (old, new) = (dir.join(old_owner), dir.join(new_owner));
+    if !old.exists() {
+        return Ok(());
+    }
+    if !new.exists() {
+        return match fs::rename(&old, &new) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Filesystem(format!("{}: {e}", old.display()))),
+        };
+    }
+    for save in dir_names(&old) {
+        let target = new.join(&save);
+        if let Err(e) = fs::create_dir_all(&target) {
+            return Err(Error::Filesystem(format!("{}: {e}", target.display())));
+        }
+        for backup in backups(dir, old_owner, &save) {
+            let name = match backup.path.file_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            if let Err(e) = fs::rename(&backup.path, target.join(name)) {
+                return Err(Error::Filesystem(format!("{}: {e}", backup.path.display())));
+            }
+        }
+    }
+    match fs::remove_dir_all(&old) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::Filesystem(format!("{}: {e}", old.display()))),
+    }
+}
+
+/// Creates the file for a new backup in `folder`, waiting for the next
+/// millisecond rather than replacing a backup taken in the same one.
+fn create_backup_file(folder: &Path) -> Result<(PathBuf, File), Error> {
+    loop {
+        let stamp = Local::now().format(STAMP_FORMAT);
+        let target = folder.join(format!("{stamp}{EXTENSION}"));
+        match File::options().write(true).create_new(true).open(&target) {
+            Ok(file) => return Ok((target, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                thread::sleep(Duration::from_millis(1))
+            }
+            Err(e) => return Err(Error::Filesystem(format!("{}: {e}", target.display()))),
+        }
+    }
+}
+
+fn archive(save: &Path, file: File, target: &Path) -> Result<(), Error> {
+    let name = match save.file_name() {
+        Some(name) => name,
+        None => {
+            let _ = fs::remove_file(target);
+            return Err(Error::MissingPath(save.display().to_string()));
+        }
+    };
+    let mut builder = Builder::new(GzEncoder::new(file, Compression::default()));
+    let result = builder
+        .append_dir_all(name, save)
+        .and_then(|_| builder.into_inner())
+        .and_then(|encoder| encoder.finish());
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(target);
+            Err(Error::Filesystem(format!("{}: {e}", save.display())))
+        }
+    }
+}
+
+/// Archives the save folder `save` of `owner` and drops the backups of it
+/// that `policy` no longer keeps.
+pub fn back_up_save(
+    dir: &Path,
+    owner: &str,
+    save: &Path,
+    policy: &BackupPolicy,
+) -> Result<PathBuf, Error> {
+    let name = match save.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return Err(Error::MissingPath(save.display().to_string())),
+    };
+    let folder = dir.join(owner).join(&name);
+    if let Err(e) = fs::create_dir_all(&folder) {
+        return Err(Error::Filesystem(format!("{}: {e}", folder.display())));
+    }
+    let (target, file) = create_backup_file(&folder)?;
+    archive(save, file, &target)?;
+    prune(dir, owner, &name, policy)?;
+    Ok(target)
+}
+
+/// Backs up every save in the saves folder `root` if `policy` is enabled.
+/// Returns how many saves were backed up.
+pub fn back_up_all(
+    dir: &Path,
+    owner: &str,
+    root: &Path,
+    policy: &BackupPolicy,
+) -> Result<usize, Error> {
+    if !policy.enabled {
+        return Ok(0);
+    }
+    let mut count = 0;
+    for name in dir_names(root) {
+        let save = root.join(name);
+        if is_save(&save) {
+            back_up_save(dir, owner, &save, policy)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Indices of the `backups`, newest first, that `policy` keeps.
+fn kept(backups: &[Backup], policy: &BackupPolicy) -> BTreeSet<usize> {
+    let mut keep = BTreeSet::from([0]);
+    keep.extend(0..policy.keep_last.min(backups.len()));
+
+    let mut days = BTreeSet::new();
+    let mut weeks = BTreeSet::new();
+    for (index, backup) in backups.iter().enumerate() {
+        let day = backup.time.date();
+        if days.len() < policy.keep_daily && days.insert(day) {
+            keep.insert(index);
+        }
+        let week = (day.iso_week().year(), day.iso_week().week());
+        if weeks.len() < policy.keep_weekly && weeks.insert(week) {
+            keep.insert(index);
+        }
+    }
+    keep
+}
+
+fn prune(dir: &Path, owner: &str, save: &str, policy: &BackupPolicy) -> Result<(), Error> {
+    let backups = backups(dir, owner, save);
+    let keep = kept(&backups, policy);
+    for (index, backup) in backups.iter().enumerate() {
+        if keep.contains(&index) {
+            continue;
+        }
+        if let Err(e) = fs::remove_file(&backup.path) {
+            return Err(Error::Filesystem(format!("{}: {e}", backup.path.display())));
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the save in the saves folder `root` with `backup`. The save is
+/// backed up to `dir` first, so the restore can be undone as well.
+pub fn restore(
+    dir: &Path,
+    backup: &Backup,
+    root: &Path,
+    policy: &BackupPolicy,
+) -> Result<(), Error> {
+    let staging = root.join(".restoring");
+    if staging.exists() {
+        if let Err(e) = fs::remove_dir_all(&staging) {
+            return Err(Error::Filesystem(format!("{}: {e}", staging.display())));
+        }
+    }
+    let file = match File::open(&backup.path) {
+        Ok(file) => file,
+        Err(e) => return Err(Error::Filesystem(format!("{}: {e}", backup.path.display()))),
+    };
+    // the archive is unpacked before backing up, which may prune it
+    if let Err(e) = Archive::new(GzDecoder::new(file)).unpack(&staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(Error::Filesystem(format!("{}: {e}", backup.path.display())));
+    }
+    let restored = staging.join(&backup.save);
+    if !is_save(&restored) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(Error::Filesystem(format!(
+            "{} doesn't contain the save {}",
+            backup.path.display(),
+            backup.save
+        )));
+    }
+
+    // the current save is only deleted once the restored one took its place
+    let current = root.join(&backup.save);
+    let replaced = root.join(format!(".{}.replaced", backup.save));
+    let moved_aside = current.exists();
+    if moved_aside {
+        if let Err(e) = back_up_save(dir, &backup.owner, &current, policy) {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+        if replaced.exists() {
+            let _ = fs::remove_dir_all(&replaced);
+        }
+        if let Err(e) = fs::rename(&current, &replaced) {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(Error::Filesystem(format!("{}: {e}", current.display())));
+        }
+    }
+    if let Err(e) = fs::rename(&restored, &current) {
+        if moved_aside {
+            let _ = fs::rename(&replaced, &current);
+        }
+        let _ = fs::remove_dir_all(&staging);
+        return Err(Error::Filesystem(format!("{}: {e}", restored.display())));
+    }
+    if moved_aside {
+        let _ = fs::remove_dir_all(&replaced);
+    }
+    let _ = fs::remove_dir_all(&staging);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backups taken at `times`, given newest first like `backups` returns them.
+    fn taken_at(times: &[&str]) -> Vec<Backup> {
+        times
+            .iter()
+            .map(|time| Backup {
+                owner: SHARED_OWNER.to_string(),
+                save: "Farm_1".to_string(),
+                path: PathBuf::from(format!("{time}{EXTENSION}")),
+                time: NaiveDateTime::parse_from_str(time, STAMP_FORMAT).unwrap(),
+                size: 0,
+            })
+            .collect()
+    }
+
+    fn policy(keep_last: usize, keep_daily: usize, keep_weekly: usize) -> BackupPolicy {
+        BackupPolicy {
+            enabled: true,
+            keep_last,
+            keep_daily,
+            keep_weekly,
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_backups() {
+        let backups = taken_at(&[
+            "2024-03-10_12-00-00",
+            "2024-03-10_11-00-00",
+            "2024-03-10_10-00-00",
+            "2024-03-10_09-00-00",
+        ]);
+        assert_eq!(kept(&backups, &policy(2, 0, 0)), BTreeSet::from([0, 1]));
+    }
+
+    #[test]
+    fn keeps_the_newest_backup_of_each_day() {
+        let backups = taken_at(&[
+            "2024-03-10_12-00-00",
+            "2024-03-10_08-00-00",
+            "2024-03-09_20-00-00",
+            "2024-03-09_10-00-00",
+            "2024-03-08_10-00-00",
+            "2024-03-07_10-00-00",
+        ]);
+        assert_eq!(kept(&backups, &policy(0, 3, 0)), BTreeSet::from([0, 2, 4]));
+    }
+
+    #[test]
+    fn keeps_the_newest_backup_of_each_week() {
+        // 2024-03-11 starts a new ISO week, 2024-03-04 the one before
+        let backups = taken_at(&[
+            "2024-03-12_10-00-00",
+            "2024-03-11_10-00-00",
+            "2024-03-10_10-00-00",
+            "2024-03-04_10-00-00",
+            "2024-02-28_10-00-00",
+        ]);
+        assert_eq!(kept(&backups, &policy(0, 0, 2)), BTreeSet::from([0, 2]));
+    }
+
+    #[test]
+    fn rules_add_up() {
+        let backups = taken_at(&[
+            "2024-03-12_10-00-00",
+            "2024-03-12_09-00-00",
+            "2024-03-11_10-00-00",
+            "2024-03-10_10-00-00",
+            "2024-03-03_10-00-00",
+        ]);
+        assert_eq!(
+            kept(&backups, &policy(2, 2, 3)),
+            BTreeSet::from([0, 1, 2, 3, 4])
+        );
+        assert_eq!(kept(&backups, &policy(1, 2, 1)), BTreeSet::from([0, 2]));
+    }
+
+    #[test]
+    fn backups_in_the_same_second_are_kept() {
+        let root = std::env::temp_dir().join(format!(
+            "smapi-instance-manager-test-{}-backups",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        let save = root.join("Saves").join("Farm_1");
+        fs::create_dir_all(&save).unwrap();
+        fs::write(save.join("SaveGameInfo"), "<Farmer />").unwrap();
+
+        let dir = root.join("backups");
+        let first = back_up_save(&dir, SHARED_OWNER, &save, &policy(5, 0, 0)).unwrap();
+        let second = back_up_save(&dir, SHARED_OWNER, &save, &policy(5, 0, 0)).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(backups(&dir, SHARED_OWNER, "Farm_1").len(), 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn always_keeps_the_newest_backup() {
+        let backups = taken_at(&["2024-03-12_10-00-00", "2024-03-11_10-00-00"]);
+        assert_eq!(kept(&backups, &policy(0, 0, 0)), BTreeSet::from([0]));
+    }
+}
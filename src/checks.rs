@@ -0,0 +1,277 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+};
+
+use serde::Serialize;
+
+use crate::mods::{InstalledMod, ModScan};
+
+/// Compares SMAPI-style semantic versions such as `1.2.3` or `1.4.0-beta.2`.
+/// Missing numeric parts count as zero and a prerelease sorts before its release.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_release, a_tag) = split_version(a);
+    let (b_release, b_tag) = split_version(b);
+
+    let len = a_release.len().max(b_release.len());
+    for i in 0..len {
+        let a_part = a_release.get(i).copied().unwrap_or(0);
+        let b_part = b_release.get(i).copied().unwrap_or(0);
+        match a_part.cmp(&b_part) {
+            Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+
+    match (a_tag, b_tag) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a_tag), Some(b_tag)) => compare_prereleases(a_tag, b_tag),
+    }
+}
+
+/// Compares prerelease tags like semver: dot-separated parts one by one,
+/// numbers numerically and below words, and fewer parts first when all
+/// shared parts are equal.
+fn compare_prereleases(a: &str, b: &str) -> Ordering {
+    let a = a.to_lowercase();
+    let b = b.to_lowercase();
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        let (a_part, b_part) = match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_part), Some(b_part)) => (a_part, b_part),
+        };
+        let order = match (a_part.parse::<u64>(), b_part.parse::<u64>()) {
+            (Ok(a_number), Ok(b_number)) => a_number.cmp(&b_number),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a_part.cmp(b_part),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+fn split_version(version: &str) -> (Vec<u64>, Option<&str>) {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    // build metadata doesn't affect precedence
+    let version = match version.split_once('+') {
+        Some((version, _)) => version,
+        None => version,
+    };
+    let (release, tag) = match version.split_once('-') {
+        Some((release, tag)) => (release, Some(tag)),
+        None => (version, None),
+    };
+    let parts = release
+        .split('.')
+        .map(|part| part.trim().parse::<u64>().unwrap_or(0))
+        .collect();
+    (parts, tag)
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DependencyProblem {
+    Missing,
+    Outdated { installed: String },
+    OptionalMissing,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DependencyIssue {
+    pub mod_name: String,
+    pub mod_id: String,
+    pub dependency_id: String,
+    pub minimum_version: Option<String>,
+    pub problem: DependencyProblem,
+}
+
+impl DependencyIssue {
+    /// Optional dependencies that are simply not installed don't stop SMAPI
+    /// from loading the mod.
+    pub fn is_blocking(&self) -> bool {
+        !matches!(self.problem, DependencyProblem::OptionalMissing)
+    }
+}
+
+/// Enabled mods sharing a UniqueID, which SMAPI refuses to load.
+#[derive(Serialize, Debug, Clone)]
+pub struct DuplicateMod {
+    pub unique_id: String,
+    /// Every copy, newest version first.
+    pub copies: Vec<InstalledMod>,
+}
+
+impl DuplicateMod {
+    pub fn newest(&self) -> &InstalledMod {
+        &self.copies[0]
+    }
+
+    pub fn older_copies(&self) -> &[InstalledMod] {
+        &self.copies[1..]
+    }
+
+    /// Whether another copy has the same version as the newest one, in which
+    /// case the kept copy is only the first by path.
+    pub fn newest_is_tied(&self) -> bool {
+        compare_versions(
+            &self.copies[0].manifest.version,
+            &self.copies[1].manifest.version,
+        ) == Ordering::Equal
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Report {
+    pub dependency_issues: Vec<DependencyIssue>,
+    pub duplicates: Vec<DuplicateMod>,
+}
+
+impl Report {
+    /// Checks every enabled mod in `scan` against the other enabled mods.
+    pub fn check(scan: &ModScan) -> Report {
+        let enabled: Vec<&InstalledMod> = scan.mods.iter().filter(|m| m.enabled).collect();
+
+        let mut installed: HashMap<String, &InstalledMod> = HashMap::new();
+        for installed_mod in &enabled {
+            installed.insert(
+                installed_mod.manifest.unique_id.to_lowercase(),
+                installed_mod,
+            );
+        }
+
+        let mut report = Report {
+            duplicates: find_duplicates(&enabled),
+            ..Report::default()
+        };
+        for installed_mod in &enabled {
+            let manifest = &installed_mod.manifest;
+            let mut requirements: Vec<(&String, &Option<String>, bool)> = manifest
+                .dependencies
+                .iter()
+                .map(|d| (&d.unique_id, &d.minimum_version, d.is_required))
+                .collect();
+            if let Some(parent) = &manifest.content_pack_for {
+                requirements.push((&parent.unique_id, &parent.minimum_version, true));
+            }
+
+            for (dependency_id, minimum_version, is_required) in requirements {
+                let problem = match installed.get(&dependency_id.to_lowercase()) {
+                    None if is_required => DependencyProblem::Missing,
+                    None => DependencyProblem::OptionalMissing,
+                    Some(dependency) => match minimum_version {
+                        Some(minimum)
+                            if compare_versions(&dependency.manifest.version, minimum)
+                                == Ordering::Less =>
+                        {
+                            DependencyProblem::Outdated {
+                                installed: dependency.manifest.version.clone(),
+                            }
+                        }
+                        _ => continue,
+                    },
+                };
+                report.dependency_issues.push(DependencyIssue {
+                    mod_name: manifest.name.clone(),
+                    mod_id: manifest.unique_id.clone(),
+                    dependency_id: dependency_id.clone(),
+                    minimum_version: minimum_version.clone(),
+                    problem,
+                });
+            }
+        }
+        report
+    }
+
+    pub fn has_blocking(&self) -> bool {
+        !self.duplicates.is_empty() || self.dependency_issues.iter().any(|i| i.is_blocking())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dependency_issues.is_empty() && self.duplicates.is_empty()
+    }
+}
+
+fn find_duplicates(enabled: &[&InstalledMod]) -> Vec<DuplicateMod> {
+    let mut by_id: BTreeMap<String, Vec<InstalledMod>> = BTreeMap::new();
+    for installed_mod in enabled {
+        by_id
+            .entry(installed_mod.manifest.unique_id.to_lowercase())
+            .or_default()
+            .push((*installed_mod).clone());
+    }
+
+    by_id
+        .into_values()
+        .filter(|copies| copies.len() > 1)
+        .map(|mut copies| {
+            copies.sort_by(|a, b| {
+                compare_versions(&b.manifest.version, &a.manifest.version)
+                    .then_with(|| a.path.cmp(&b.path))
+            });
+            DuplicateMod {
+                unique_id: copies[0].manifest.unique_id.clone(),
+                copies,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_parts_compare_numerically() {
+        assert_eq!(compare_versions("1.10.0", "1.9.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("v2.0.0", "2.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0.1", "1.0.0.5"), Ordering::Greater);
+    }
+
+    #[test]
+    fn prerelease_sorts_before_release() {
+        assert_eq!(compare_versions("1.0.0-beta", "1.0.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0", "1.0.0-rc.1"), Ordering::Greater);
+    }
+
+    #[test]
+    fn numeric_prerelease_parts_compare_numerically() {
+        assert_eq!(
+            compare_versions("1.0.0-beta.10", "1.0.0-beta.2"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions("1.0.0-alpha.1", "1.0.0-alpha.beta"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("1.0.0-alpha", "1.0.0-alpha.1"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("1.0.0-beta", "1.0.0-alpha.5"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions("1.0.0-Beta.2", "1.0.0-beta.2"),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn build_metadata_is_ignored() {
+        assert_eq!(compare_versions("1.0.0+build.5", "1.0.0"), Ordering::Equal);
+        assert_eq!(
+            compare_versions("1.0.0-rc.1+abc", "1.0.0-rc.1"),
+            Ordering::Equal
+        );
+    }
+}
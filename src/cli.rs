@@ -0,0 +1,347 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+
+use crate::app::{Instance, Manager};
+use crate::backup;
+use crate::checks::{DependencyProblem, Report};
+use crate::error::Error;
+use crate::output::{CheckResult, InstanceInfo, InstanceList, InstancePath, ModInventory, Output};
+use crate::saves;
+use crate::session::format_duration;
+use crate::summary::SessionSummary;
+
+/// Manage multiple SMAPI mod folders. Starts the TUI when no command is given.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+    /// Print machine-readable JSON for list, path, mods and check
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Config file to use instead of the one in the XDG config directory
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// List all instances and their mod folders
+    List,
+    /// Add a new instance
+    Add {
+        name: String,
+        folder_name: String,
+        /// Use a different SMAPI installation for this instance
+        #[arg(long)]
+        smapi_path: Option<String>,
+        /// Keep this instance's saves in their own folder
+        #[arg(long)]
+        saves_dir: Option<String>,
+    },
+    /// Remove an instance
+    Remove {
+        name: String,
+        /// Delete the instance's mod folder as well
+        #[arg(long)]
+        with_folder: bool,
+    },
+    /// Launch SMAPI with an instance's mods
+    Run {
+        instance: String,
+        /// Launch even if required dependencies are unresolved or the mods
+        /// folder is already in use
+        #[arg(long)]
+        force: bool,
+    },
+    /// Open an instance's mod folder in the file manager
+    Open { instance: String },
+    /// Print the mod folder of an instance
+    Path { instance: String },
+    /// List the mods installed in an instance
+    Mods { instance: String },
+    /// Check an instance for missing dependencies and duplicate mods
+    Check { instance: String },
+    /// Replace the config with the newest backup
+    RestoreConfig,
+    /// Copy an instance's mod folder into a new instance
+    Clone {
+        source: String,
+        name: String,
+        folder_name: String,
+        /// Only copy manifest.json and config.json files
+        #[arg(long)]
+        configs_only: bool,
+    },
+    /// List the backups taken of saves before launching
+    Backups {
+        /// Only list the backups of this save folder
+        save: Option<String>,
+    },
+    /// Roll a save folder back to a backup
+    RestoreSave {
+        save: String,
+        /// Restore a save of this instance's own saves folder
+        #[arg(long)]
+        instance: Option<String>,
+        /// Time of the backup as listed by `backups`, the newest if not given
+        #[arg(long)]
+        at: Option<String>,
+    },
+}
+
+pub fn run(command: Commands, json: bool, config_path: &Path) -> Result<(), Error> {
+    if let Commands::RestoreConfig = command {
+        let backup = Manager::restore_previous_config(config_path)?;
+        println!("Restored {}", backup.display());
+        return Ok(());
+    }
+
+    if !config_path.exists() {
+        return Err(Error::MissingPath(format!(
+            "no config at {}, run without a command to set it up",
+            config_path.display()
+        )));
+    }
+    let mut manager = match Manager::load_or_recover(config_path) {
+        Ok((manager, warning)) => {
+            if let Some(warning) = warning {
+                eprintln!("Warning: {warning}");
+            }
+            manager
+        }
+        Err(e) => return Err(e),
+    };
+
+    match command {
+        Commands::List => {
+            if json {
+                let instances = manager
+                    .instances
+                    .iter()
+                    .map(|(name, instance)| InstanceInfo {
+                        name: name.clone(),
+                        folder_name: instance.folder_name.clone(),
+                        smapi_path: instance.smapi_path.clone(),
+                        path: manager.get_path(instance.clone()),
+                    })
+                    .collect();
+                return Output::print(InstanceList { instances });
+            }
+            for (name, instance) in &manager.instances {
+                println!("{: <25}{}", name, manager.get_path(instance.clone()));
+            }
+        }
+        Commands::Add {
+            name,
+            folder_name,
+            smapi_path,
+            saves_dir,
+        } => {
+            manager.check_new_name(&name)?;
+            let mut instance = Instance::new(folder_name, smapi_path);
+            instance.saves_dir = saves_dir;
+            manager.add_instance(instance, name);
+            manager.save()?;
+        }
+        Commands::Remove { name, with_folder } => {
+            manager.remove_instance(name, with_folder)?;
+        }
+        Commands::Run { instance, force } => {
+            let report = Report::check(&manager.scan_mods(manager.get_instance(&instance)?));
+            if report.has_blocking() && !force {
+                return Err(Error::Instance(
+                    "unresolved dependencies or duplicate mods found, check them in the TUI or pass --force".into(),
+                ));
+            }
+            if let Some(lock) = manager.running_lock(&instance)? {
+                if !force {
+                    return Err(Error::Instance(format!(
+                        "{} (pid {}) is already running from this mods folder, stop it or pass --force",
+                        lock.instance, lock.pid
+                    )));
+                }
+            }
+            if let Some(swapped) = saves::recover(&manager.state_dir())? {
+                eprintln!("Restored the shared saves swapped out for {swapped}");
+            }
+            let summary = manager.run(&instance)?;
+            print_summary(&summary);
+        }
+        Commands::Open { instance } => {
+            let instance = manager.get_instance(&instance)?;
+            manager.open(instance)?;
+        }
+        Commands::Path { instance: name } => {
+            let instance = manager.get_instance(&name)?;
+            let path = manager.get_path(instance);
+            if json {
+                return Output::print(InstancePath {
+                    instance: name,
+                    path,
+                });
+            }
+            println!("{path}");
+        }
+        Commands::Mods { instance: name } => {
+            let instance = manager.get_instance(&name)?;
+            let scan = manager.scan_mods(instance.clone());
+            if json {
+                return Output::print(ModInventory {
+                    instance: name,
+                    path: manager.get_path(instance),
+                    scan,
+                });
+            }
+            for installed in &scan.mods {
+                let manifest = &installed.manifest;
+                let disabled = if installed.enabled { "" } else { " (disabled)" };
+                println!(
+                    "{: <35}{: <12}{}{disabled}",
+                    manifest.name, manifest.version, manifest.author
+                );
+            }
+            for failed in &scan.errors {
+                eprintln!("{}: {}", failed.path.display(), failed.error);
+            }
+        }
+        Commands::Check { instance: name } => {
+            let instance = manager.get_instance(&name)?;
+            let report = Report::check(&manager.scan_mods(instance));
+            let blocking = report.has_blocking();
+            if json {
+                Output::print(CheckResult {
+                    instance: name,
+                    blocking,
+                    report,
+                })?;
+            } else {
+                print_report(&report);
+            }
+            if blocking {
+                return Err(Error::Instance("instance has blocking problems".into()));
+            }
+        }
+        // Handled before loading, as it has to work with a corrupt config.
+        Commands::RestoreConfig => (),
+        Commands::Clone {
+            source,
+            name,
+            folder_name,
+            configs_only,
+        } => {
+            manager.clone_instance(
+                source,
+                name,
+                folder_name,
+                configs_only,
+                &mut |done, total| {
+                    eprint!("\rCopied {done}/{total} files");
+                },
+            )?;
+            eprintln!();
+        }
+        Commands::Backups { save } => {
+            for backup in backup::all_backups(&manager.backup_dir()) {
+                if save.as_ref().is_some_and(|save| *save != backup.save) {
+                    continue;
+                }
+                println!(
+                    "{: <15}{: <35}{}",
+                    backup.owner,
+                    backup.save,
+                    backup.time.format(BACKUP_TIME)
+                );
+            }
+        }
+        Commands::RestoreSave { save, instance, at } => {
+            let owner = match instance {
+                Some(name) => {
+                    let instance = manager.get_instance(&name)?;
+                    manager.saves_owner(&name, &instance)
+                }
+                None => backup::SHARED_OWNER.to_string(),
+            };
+            let backups = backup::backups(&manager.backup_dir(), &owner, &save);
+            let backup = match &at {
+                Some(at) => backups
+                    .iter()
+                    .find(|backup| backup.time.format(BACKUP_TIME).to_string() == *at),
+                None => backups.first(),
+            };
+            let backup = match backup {
+                Some(backup) => backup,
+                None => {
+                    return Err(Error::MissingPath(format!(
+                        "no backup of {save} in the {owner} saves{}",
+                        at.map(|at| format!(" taken at {at}")).unwrap_or_default()
+                    )))
+                }
+            };
+            manager.restore_save(backup)?;
+            println!("Restored {save} from {}", backup.time.format(BACKUP_TIME));
+        }
+    }
+    Ok(())
+}
+
+/// How `backups` prints the time of a backup, which `restore-save --at` takes.
+const BACKUP_TIME: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+fn print_summary(summary: &SessionSummary) {
+    let duration = format_duration(Duration::from_secs(summary.duration_secs));
+    match summary.exit_code {
+        Some(code) => eprintln!(
+            "{} exited with code {code} after {duration}",
+            summary.instance
+        ),
+        None => eprintln!("{} was stopped after {duration}", summary.instance),
+    }
+    for skipped in &summary.skipped_mods {
+        eprintln!("Skipped: {skipped}");
+    }
+    for problems in &summary.problems {
+        eprintln!(
+            "{}: {} error(s), {} warning(s)",
+            problems.source, problems.errors, problems.warnings
+        );
+    }
+}
+
+fn print_report(report: &Report) {
+    if report.is_empty() {
+        println!("No problems found");
+    }
+    for duplicate in &report.duplicates {
+        println!(
+            "{} is installed {} times:",
+            duplicate.unique_id,
+            duplicate.copies.len()
+        );
+        for copy in &duplicate.copies {
+            println!("    {} at {}", copy.manifest.version, copy.path.display());
+        }
+    }
+    for issue in &report.dependency_issues {
+        let minimum = match &issue.minimum_version {
+            Some(version) => format!(" >= {version}"),
+            None => String::new(),
+        };
+        match &issue.problem {
+            DependencyProblem::Missing => println!(
+                "{}: requires {}{minimum}, which is not installed",
+                issue.mod_name, issue.dependency_id
+            ),
+            DependencyProblem::Outdated { installed } => println!(
+                "{}: requires {}{minimum}, but {installed} is installed",
+                issue.mod_name, issue.dependency_id
+            ),
+            DependencyProblem::OptionalMissing => println!(
+                "{}: can use {}{minimum}, which is not installed",
+                issue.mod_name, issue.dependency_id
+            ),
+        }
+    }
+}
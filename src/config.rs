@@ -0,0 +1,449 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::backup::BackupPolicy;
+use crate::error::Error;
+use crate::launch::LaunchTemplate;
+
+/// Schema version written to `config.json`. Bump it together with a new entry
+/// in `MIGRATIONS` whenever the layout of `Manager` or `Instance` changes.
+pub const CONFIG_VERSION: u64 = 5;
+
+type Migration = fn(&mut Value) -> Result<(), Error>;
+
+/// `MIGRATIONS[n]` upgrades a config from version `n` to `n + 1`.
+const MIGRATIONS: [Migration; CONFIG_VERSION as usize] =
+    [migrate_v0, migrate_v1, migrate_v2, migrate_v3, migrate_v4];
+
+/// Configs from before versioning have no `version` field.
+fn migrate_v0(config: &mut Value) -> Result<(), Error> {
+    match config.as_object_mut() {
+        Some(object) => {
+            object.insert("version".into(), Value::from(1));
+            Ok(())
+        }
+        None => Err(Error::Parse("config is not a JSON object".into())),
+    }
+}
+
+/// Launch templates replaced the hard-coded `$TERMINAL -e steam-run` command,
+/// which stays the default for existing configs.
+fn migrate_v1(config: &mut Value) -> Result<(), Error> {
+    let launch = match serde_json::to_value(LaunchTemplate::default()) {
+        Ok(launch) => launch,
+        Err(e) => return Err(Error::Parse(e.to_string())),
+    };
+    match config.as_object_mut() {
+        Some(object) => {
+            object.insert("launch".into(), launch);
+            object.insert("version".into(), Value::from(2));
+            Ok(())
+        }
+        None => Err(Error::Parse("config is not a JSON object".into())),
+    }
+}
+
+/// Launch templates gained a mode, which is `terminal` for everything so far.
+fn migrate_v2(config: &mut Value) -> Result<(), Error> {
+    let object = match config.as_object_mut() {
+        Some(object) => object,
+        None => return Err(Error::Parse("config is not a JSON object".into())),
+    };
+    if let Some(Value::Object(launch)) = object.get_mut("launch") {
+        launch.insert("mode".into(), Value::from("terminal"));
+    }
+    if let Some(Value::Object(instances)) = object.get_mut("instances") {
+        for instance in instances.values_mut() {
+            if let Some(Value::Object(launch)) = instance.get_mut("launch") {
+                launch.insert("mode".into(), Value::from("terminal"));
+            }
+        }
+    }
+    object.insert("version".into(), Value::from(3));
+    Ok(())
+}
+
+/// Instances gained an optional saves folder. Nothing changes for existing
+/// instances, but older builds would drop the field when saving.
+fn migrate_v3(config: &mut Value) -> Result<(), Error> {
+    match config.as_object_mut() {
+        Some(object) => {
+            object.insert("version".into(), Value::from(4));
+            Ok(())
+        }
+        None => Err(Error::Parse("config is not a JSON object".into())),
+    }
+}
+
+/// Saves are backed up before launching, with the default retention.
+fn migrate_v4(config: &mut Value) -> Result<(), Error> {
+    let backups = match serde_json::to_value(BackupPolicy::default()) {
+        Ok(backups) => backups,
+        Err(e) => return Err(Error::Parse(e.to_string())),
+    };
+    match config.as_object_mut() {
+        Some(object) => {
+            object.insert("backups".into(), backups);
+            object.insert("version".into(), Value::from(5));
+            Ok(())
+        }
+        None => Err(Error::Parse("config is not a JSON object".into())),
+    }
+}
+
+/// Upgrades `config` to `CONFIG_VERSION`, backing up the file at `path` first.
+/// Returns whether anything was migrated.
+pub fn migrate(config: &mut Value, path: &Path) -> Result<bool, Error> {
+    let version = match config.get("version") {
+        Some(version) => match version.as_u64() {
+            Some(v) => v,
+            None => return Err(Error::Parse(format!("invalid config version {version}"))),
+        },
+        None => 0,
+    };
+    if version > CONFIG_VERSION {
+        return Err(Error::Parse(format!(
+            "{} uses config version {version}, but this build only supports up to version {CONFIG_VERSION}; please update SMAPI-Instance-Manager",
+            path.display()
+        )));
+    }
+    if version == CONFIG_VERSION {
+        return Ok(false);
+    }
+
+    let backup = path.with_extension(format!("v{version}.json"));
+    if let Err(e) = fs::copy(path, &backup) {
+        return Err(Error::ConfigIo(format!(
+            "failed to back up config to {}: {e}",
+            backup.display()
+        )));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(config)?;
+    }
+    Ok(true)
+}
+
+/// Where versions before the XDG layout kept their config.
+const LEGACY_CONFIG: &str = "./config.json";
+
+/// `$XDG_CONFIG_HOME/smapi-instance-manager`, falling back to `~/.config`.
+pub fn config_dir() -> PathBuf {
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var("HOME") {
+            Ok(home) => Path::new(home.as_str()).join(".config"),
+            Err(_) => PathBuf::from("."),
+        },
+    };
+    base.join("smapi-instance-manager")
+}
+
+/// `$XDG_DATA_HOME/smapi-instance-manager`, falling back to `~/.local/share`.
+pub fn data_dir() -> PathBuf {
+    let base = match env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var("HOME") {
+            Ok(home) => Path::new(home.as_str()).join(".local/share"),
+            Err(_) => PathBuf::from("."),
+        },
+    };
+    base.join("smapi-instance-manager")
+}
+
+pub fn default_config_path() -> PathBuf {
+    config_dir().join("config.json")
+}
+
+/// Moves a `./config.json` left by older versions to `path` if nothing is
+/// there yet. Returns whether a config was migrated.
+pub fn migrate_legacy_config(path: &Path) -> Result<bool, Error> {
+    let legacy = Path::new(LEGACY_CONFIG);
+    if path.exists() || !legacy.is_file() {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(Error::ConfigIo(format!("{}: {e}", parent.display())));
+        }
+    }
+    if let Err(e) = fs::copy(legacy, path) {
+        return Err(Error::ConfigIo(format!("{}: {e}", path.display())));
+    }
+    if let Err(e) = fs::remove_file(legacy) {
+        return Err(Error::ConfigIo(format!("{}: {e}", legacy.display())));
+    }
+    Ok(true)
+}
+
+/// Number of previous configs kept next to the config as `config.json.1` (newest)
+/// up to `config.json.5`.
+pub const BACKUP_COUNT: usize = 5;
+
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+/// Existing backups of the config at `path`, newest first.
+pub fn backups(path: &Path) -> Vec<PathBuf> {
+    (1..=BACKUP_COUNT)
+        .map(|index| backup_path(path, index))
+        .filter(|backup| backup.is_file())
+        .collect()
+}
+
+fn rotate_backups(path: &Path) -> Result<(), Error> {
+    for index in (1..BACKUP_COUNT).rev() {
+        let from = backup_path(path, index);
+        if from.is_file() {
+            if let Err(e) = fs::rename(&from, backup_path(path, index + 1)) {
+                return Err(Error::ConfigIo(format!("{}: {e}", from.display())));
+            }
+        }
+    }
+    if let Err(e) = fs::copy(path, backup_path(path, 1)) {
+        return Err(Error::ConfigIo(format!("{}: {e}", path.display())));
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` with `contents` without ever leaving a
+/// truncated file behind: the data is written and synced to a temporary file
+/// that is then renamed over the old one, which is kept as a backup.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(Error::ConfigIo(format!("{}: {e}", parent.display())));
+        }
+    }
+
+    let mut temp_name = path.as_os_str().to_os_string();
+    temp_name.push(".tmp");
+    let temp = PathBuf::from(temp_name);
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(Error::ConfigIo(format!("{}: {e}", temp.display())));
+    }
+
+    if path.is_file() {
+        match fs::read(path) {
+            Ok(old) if old == contents => {
+                let _ = fs::remove_file(&temp);
+                return Ok(());
+            }
+            _ => rotate_backups(path)?,
+        }
+    }
+    if let Err(e) = fs::rename(&temp, path) {
+        return Err(Error::ConfigIo(format!("{}: {e}", path.display())));
+    }
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Whether the config at `path` was written by a newer build, in which case
+/// it must not be replaced by a backup.
+pub fn is_from_newer_version(path: &Path) -> bool {
+    let contents = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return false,
+    };
+    match serde_json::from_str::<Value>(contents.as_str()) {
+        Ok(config) => match config.get("version").and_then(|v| v.as_u64()) {
+            Some(version) => version > CONFIG_VERSION,
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::app::Manager;
+    use crate::launch::{LaunchMode, LaunchTemplate};
+
+    /// Writes `config` to a fresh file, as `migrate` backs the file up first.
+    fn config_file(name: &str, config: &Value) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "smapi-instance-manager-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        fs::write(&path, config.to_string()).unwrap();
+        path
+    }
+
+    fn migrate_to_manager(name: &str, mut config: Value) -> Manager {
+        let path = config_file(name, &config);
+        let version = config.get("version").and_then(Value::as_u64).unwrap_or(0);
+        assert!(migrate(&mut config, &path).unwrap());
+        assert!(path.with_extension(format!("v{version}.json")).is_file());
+        assert_eq!(config["version"], CONFIG_VERSION);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+        serde_json::from_value(config).unwrap()
+    }
+
+    fn unversioned() -> Value {
+        json!({
+            "instances": {
+                "Default": { "folder_name": "Mods", "smapi_path": null },
+                "Expanded": { "folder_name": "ExpandedMods", "smapi_path": "/games/sdv/StardewModdingAPI" }
+            },
+            "smapi_path": "/games/sdv/StardewModdingAPI"
+        })
+    }
+
+    fn with_version(mut config: Value, version: u64) -> Value {
+        config["version"] = json!(version);
+        config
+    }
+
+    #[test]
+    fn unversioned_config_is_migrated() {
+        let manager = migrate_to_manager("v0", unversioned());
+        assert_eq!(manager.version, CONFIG_VERSION);
+        assert_eq!(manager.instances.len(), 2);
+        assert_eq!(
+            manager.instances["Expanded"].smapi_path.as_deref(),
+            Some("/games/sdv/StardewModdingAPI")
+        );
+        assert_eq!(manager.inbox, None);
+        assert_eq!(manager.launch, LaunchTemplate::default());
+        assert_eq!(manager.backups, BackupPolicy::default());
+    }
+
+    #[test]
+    fn v1_config_gets_the_default_launch_template() {
+        let manager = migrate_to_manager("v1", with_version(unversioned(), 1));
+        assert_eq!(manager.launch, LaunchTemplate::default());
+    }
+
+    #[test]
+    fn v2_launch_templates_get_the_terminal_mode() {
+        let mut config = with_version(unversioned(), 2);
+        config["launch"] = json!({ "terminal": "kitty --", "command": "{smapi}" });
+        config["instances"]["Expanded"]["launch"] =
+            json!({ "terminal": "foot", "command": "gamemoderun {smapi}" });
+        let manager = migrate_to_manager("v2", config);
+        assert_eq!(manager.launch.terminal, "kitty --");
+        assert_eq!(manager.launch.mode, LaunchMode::Terminal);
+        let launch = manager.instances["Expanded"].launch.clone().unwrap();
+        assert_eq!(launch.command, "gamemoderun {smapi}");
+        assert_eq!(launch.mode, LaunchMode::Terminal);
+    }
+
+    #[test]
+    fn v3_and_v4_configs_are_migrated() {
+        let mut config = with_version(unversioned(), 3);
+        config["launch"] = serde_json::to_value(LaunchTemplate::default()).unwrap();
+        let manager = migrate_to_manager("v3", config.clone());
+        assert_eq!(manager.instances["Default"].saves_dir, None);
+        assert_eq!(manager.backups, BackupPolicy::default());
+
+        config["instances"]["Expanded"]["saves_dir"] = json!("~/expanded-saves");
+        let manager = migrate_to_manager("v4", with_version(config, 4));
+        assert_eq!(
+            manager.instances["Expanded"].saves_dir.as_deref(),
+            Some("~/expanded-saves")
+        );
+        assert_eq!(manager.backups, BackupPolicy::default());
+    }
+
+    #[test]
+    fn current_config_is_left_alone() {
+        let mut config = serde_json::to_value(Manager::new("/sdv".into(), PathBuf::new())).unwrap();
+        let path = config_file("current", &config);
+        let before = config.clone();
+        assert!(!migrate(&mut config, &path).unwrap());
+        assert_eq!(config, before);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    /// A fresh folder for a config and its backups.
+    fn config_dir_for(name: &str) -> PathBuf {
+        let path = config_file(name, &json!({}));
+        fs::remove_file(&path).unwrap();
+        path
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn writes_rotate_the_backups() {
+        let path = config_dir_for("rotation");
+        for index in 1..=7 {
+            write_atomic(&path, index.to_string().as_bytes()).unwrap();
+        }
+        assert_eq!(read(&path), "7");
+        let kept: Vec<String> = backups(&path).iter().map(|backup| read(backup)).collect();
+        assert_eq!(kept, ["6", "5", "4", "3", "2"]);
+        assert!(!backup_path(&path, BACKUP_COUNT + 1).exists());
+        assert!(!path.with_extension("json.tmp").exists());
+
+        // writing the same contents again doesn't push out a backup
+        write_atomic(&path, b"7").unwrap();
+        assert_eq!(read(&backup_path(&path, 1)), "6");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn corrupt_config_falls_back_to_the_newest_readable_backup() {
+        let path = config_dir_for("recovery");
+        Manager::new("/good".into(), path.clone()).save().unwrap();
+        fs::rename(&path, backup_path(&path, 2)).unwrap();
+        fs::write(backup_path(&path, 1), "{ not json").unwrap();
+        fs::write(&path, "{ not json either").unwrap();
+
+        let (manager, warning) = Manager::load_or_recover(&path).unwrap();
+        assert_eq!(manager.smapi_path, "/good");
+        assert!(warning.unwrap().contains("config.json.2"));
+        assert_eq!(Manager::load_config(&path).unwrap().smapi_path, "/good");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn restore_config_can_be_undone() {
+        let path = config_dir_for("restore");
+        Manager::new("/first".into(), path.clone()).save().unwrap();
+        Manager::new("/second".into(), path.clone()).save().unwrap();
+
+        let restored = Manager::restore_previous_config(&path).unwrap();
+        assert_eq!(restored, backup_path(&path, 1));
+        assert_eq!(Manager::load_config(&path).unwrap().smapi_path, "/first");
+
+        Manager::restore_previous_config(&path).unwrap();
+        assert_eq!(Manager::load_config(&path).unwrap().smapi_path, "/second");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn future_config_is_rejected() {
+        let mut config = with_version(unversioned(), CONFIG_VERSION + 1);
+        let path = config_file("future", &config);
+        assert!(matches!(migrate(&mut config, &path), Err(Error::Parse(_))));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
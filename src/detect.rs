@@ -0,0 +1,121 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::app::expand_home;
+use crate::error::Error;
+
+const SMAPI_EXECUTABLE: &str = "StardewModdingAPI";
+
+fn home() -> Option<PathBuf> {
+    env::var("HOME").ok().map(PathBuf::from)
+}
+
+/// Steam installs for native, legacy-symlink and Flatpak setups.
+fn steam_roots(home: &Path) -> Vec<PathBuf> {
+    vec![
+        home.join(".local/share/Steam"),
+        home.join(".steam/steam"),
+        home.join(".steam/root"),
+        home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+    ]
+}
+
+/// Reads the library paths out of Steam's `libraryfolders.vdf`, which has
+/// lines like `"path"  "/mnt/games/SteamLibrary"`.
+fn library_folders(steam_root: &Path) -> Vec<PathBuf> {
+    let contents = match fs::read_to_string(steam_root.join("steamapps/libraryfolders.vdf")) {
+        Ok(c) => c,
+        Err(_) => return Vec::new(),
+    };
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('"').filter(|field| !field.trim().is_empty());
+            match (fields.next(), fields.next()) {
+                (Some("path"), Some(path)) => Some(PathBuf::from(path.replace("\\\\", "\\"))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Game folders where Steam, GOG and Heroic put Stardew Valley by default.
+fn game_folders() -> Vec<PathBuf> {
+    let home = match home() {
+        Some(home) => home,
+        None => return Vec::new(),
+    };
+
+    let mut libraries = Vec::new();
+    for root in steam_roots(&home) {
+        libraries.push(root.clone());
+        libraries.extend(library_folders(&root));
+    }
+
+    let mut folders: Vec<PathBuf> = libraries
+        .iter()
+        .map(|library| library.join("steamapps/common/Stardew Valley"))
+        .collect();
+    folders.push(home.join("GOG Games/Stardew Valley/game"));
+    folders.push(home.join("Games/gog/stardew-valley/game"));
+    folders.push(home.join("Games/Heroic/Stardew Valley/game"));
+    folders
+}
+
+/// Where the game keeps saves and SMAPI its logs, `~/.config/StardewValley`
+/// unless `XDG_CONFIG_HOME` points elsewhere.
+pub fn game_data_dir() -> PathBuf {
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match home() {
+            Some(home) => home.join(".config"),
+            None => PathBuf::from("."),
+        },
+    };
+    base.join("StardewValley")
+}
+
+/// Paths of every `StardewModdingAPI` executable found in the usual install
+/// locations, without duplicates from symlinked Steam folders.
+pub fn find_smapi_installs() -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    for folder in game_folders() {
+        let executable = folder.join(SMAPI_EXECUTABLE);
+        if !executable.is_file() {
+            continue;
+        }
+        let canonical = fs::canonicalize(&executable).unwrap_or(executable.clone());
+        if !seen.contains(&canonical) {
+            seen.push(canonical);
+            found.push(executable);
+        }
+    }
+    found
+}
+
+/// Accepts either the game folder or the `StardewModdingAPI` executable itself
+/// and returns the path of the executable.
+pub fn validate_smapi_path(input: &str) -> Result<PathBuf, Error> {
+    let path = expand_home(input.trim());
+    let executable = if path.is_dir() {
+        path.join(SMAPI_EXECUTABLE)
+    } else {
+        path
+    };
+    if executable.file_name() != Some(SMAPI_EXECUTABLE.as_ref()) {
+        return Err(Error::MissingPath(format!(
+            "{} is not the {SMAPI_EXECUTABLE} executable",
+            executable.display()
+        )));
+    }
+    if !executable.is_file() {
+        return Err(Error::MissingPath(format!(
+            "{} does not exist",
+            executable.display()
+        )));
+    }
+    Ok(executable)
+}
@@ -0,0 +1,32 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the config file failed.
+    ConfigIo(String),
+    /// The config, a manifest or an archive couldn't be understood.
+    Parse(String),
+    /// SMAPI, the terminal or the file manager couldn't be started.
+    Launch(String),
+    /// A path that is needed doesn't exist.
+    MissingPath(String),
+    /// Copying, moving or deleting mod files failed.
+    Filesystem(String),
+    /// The instance doesn't exist or the change isn't allowed.
+    Instance(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ConfigIo(e) => write!(f, "config: {e}"),
+            Error::Parse(e) => write!(f, "parse error: {e}"),
+            Error::Launch(e) => write!(f, "failed to launch: {e}"),
+            Error::MissingPath(e) => write!(f, "missing path: {e}"),
+            Error::Filesystem(e) => write!(f, "filesystem: {e}"),
+            Error::Instance(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,109 @@
+use std::{
+    fs,
+    os::unix,
+    path::{Path, PathBuf},
+};
+
+use crate::error::Error;
+use crate::lock::LOCK_FILE;
+use crate::logs::LOG_DIR;
+
+/// Files kept when only the setup of an instance is copied.
+const CONFIG_FILES: [&str; 2] = ["manifest.json", "config.json"];
+
+/// Symlinks are collected like files and copied as links, so linked folders
+/// aren't followed out of `dir` or back into it.
+fn collect_files(dir: &Path, configs_only: bool, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => return Err(Error::Filesystem(format!("{}: {e}", dir.display()))),
+    };
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => return Err(Error::Filesystem(e.to_string())),
+        };
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(e) => return Err(Error::Filesystem(format!("{}: {e}", path.display()))),
+        };
+        if path.file_name() == Some(LOG_DIR.as_ref()) {
+            continue;
+        } else if file_type.is_dir() {
+            collect_files(&path, configs_only, files)?;
+        } else if path.file_name() == Some(LOCK_FILE.as_ref()) {
+            continue;
+        } else if !configs_only || is_config_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_config_file(path: &Path) -> bool {
+    match path.file_name() {
+        Some(name) => CONFIG_FILES.iter().any(|config| name == *config),
+        None => false,
+    }
+}
+
+/// Copies `source` into the new folder `target`, calling `progress` with the
+/// number of copied and total files. A partial copy is removed on error.
+pub fn copy_dir(
+    source: &Path,
+    target: &Path,
+    configs_only: bool,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<(), Error> {
+    if target.exists() {
+        return Err(Error::Filesystem(format!(
+            "{} already exists",
+            target.display()
+        )));
+    }
+
+    let mut files = Vec::new();
+    if source.exists() {
+        collect_files(source, configs_only, &mut files)?;
+    }
+
+    let result = copy_files(source, target, &files, progress);
+    if result.is_err() && target.exists() {
+        let _ = fs::remove_dir_all(target);
+    }
+    result
+}
+
+fn copy_files(
+    source: &Path,
+    target: &Path,
+    files: &[PathBuf],
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<(), Error> {
+    if let Err(e) = fs::create_dir_all(target) {
+        return Err(Error::Filesystem(format!("{}: {e}", target.display())));
+    }
+    progress(0, files.len());
+    for (index, file) in files.iter().enumerate() {
+        let relative = match file.strip_prefix(source) {
+            Ok(relative) => relative,
+            Err(e) => return Err(Error::Filesystem(e.to_string())),
+        };
+        let destination = target.join(relative);
+        if let Some(parent) = destination.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                return Err(Error::Filesystem(format!("{}: {e}", parent.display())));
+            }
+        }
+        let result = match fs::read_link(file) {
+            Ok(link) => unix::fs::symlink(link, &destination),
+            Err(_) => fs::copy(file, &destination).map(|_| ()),
+        };
+        if let Err(e) = result {
+            return Err(Error::Filesystem(format!("{}: {e}", file.display())));
+        }
+        progress(index + 1, files.len());
+    }
+    Ok(())
+}
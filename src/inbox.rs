@@ -0,0 +1,166 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// How long an archive's size has to stay the same before it counts as done.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// Suffixes browsers give downloads in progress, next to the final name.
+const PARTIAL_SUFFIXES: [&str; 2] = ["part", "crdownload"];
+
+/// Watches a downloads folder for mod archives that appear while the manager runs.
+pub struct Inbox {
+    pub path: PathBuf,
+    seen: HashSet<PathBuf>,
+    /// New archives that may still be downloading, with their size and when
+    /// it last changed.
+    pending: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl Inbox {
+    /// Archives already in the folder are remembered so only new downloads are offered.
+    pub fn new(path: PathBuf) -> Inbox {
+        let seen = list_archives(&path).into_iter().collect();
+        Inbox {
+            path,
+            seen,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Returns archives that appeared and finished downloading since the last
+    /// poll. Firefox creates an empty `.zip` next to the `.zip.part` it
+    /// downloads to, so empty archives, archives with such a sibling and
+    /// archives that are still growing are held back.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut new_archives = Vec::new();
+        for archive in list_archives(&self.path) {
+            if self.seen.contains(&archive) {
+                continue;
+            }
+            let size = match fs::metadata(&archive) {
+                Ok(metadata) => metadata.len(),
+                Err(_) => continue,
+            };
+            if size == 0 || is_downloading(&archive) {
+                self.pending.remove(&archive);
+                continue;
+            }
+            match self.pending.get(&archive) {
+                Some((last_size, since)) if *last_size == size => {
+                    if since.elapsed() >= SETTLE_TIME {
+                        self.pending.remove(&archive);
+                        self.seen.insert(archive.clone());
+                        new_archives.push(archive);
+                    }
+                }
+                _ => {
+                    self.pending.insert(archive, (size, Instant::now()));
+                }
+            }
+        }
+        new_archives
+    }
+}
+
+fn is_downloading(archive: &Path) -> bool {
+    PARTIAL_SUFFIXES.iter().any(|suffix| {
+        let mut partial = archive.as_os_str().to_os_string();
+        partial.push(format!(".{suffix}"));
+        Path::new(&partial).exists()
+    })
+}
+
+fn list_archives(path: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(path) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let mut archives: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| match path.extension() {
+            Some(extension) => extension.eq_ignore_ascii_case("zip"),
+            None => false,
+        })
+        .collect();
+    archives.sort();
+    archives
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn downloads(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "smapi-instance-manager-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Pretends the size of every pending archive last changed `SETTLE_TIME` ago.
+    fn wait_for_settle(inbox: &mut Inbox) {
+        for (_, since) in inbox.pending.values_mut() {
+            *since -= SETTLE_TIME;
+        }
+    }
+
+    #[test]
+    fn archives_are_offered_once_their_size_settles() {
+        let dir = downloads("inbox-settle");
+        fs::write(dir.join("old.zip"), "old").unwrap();
+        let mut inbox = Inbox::new(dir.clone());
+
+        let archive = dir.join("Mod.zip");
+        fs::write(&archive, "some").unwrap();
+        fs::write(dir.join("notes.txt"), "not an archive").unwrap();
+        assert!(inbox.poll().is_empty());
+        assert!(inbox.poll().is_empty());
+
+        // still being written: the size changed, so the wait starts over
+        fs::write(&archive, "some more").unwrap();
+        wait_for_settle(&mut inbox);
+        assert!(inbox.poll().is_empty());
+
+        wait_for_settle(&mut inbox);
+        assert_eq!(inbox.poll(), [archive]);
+        assert!(inbox.poll().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn downloads_in_progress_are_held_back() {
+        let dir = downloads("inbox-partial");
+        let mut inbox = Inbox::new(dir.clone());
+
+        // Firefox leaves an empty archive next to the one it downloads to
+        let firefox = dir.join("Firefox.zip");
+        fs::write(&firefox, "").unwrap();
+        fs::write(dir.join("Firefox.zip.part"), "data").unwrap();
+        let chrome = dir.join("Chrome.zip");
+        fs::write(&chrome, "data").unwrap();
+        fs::write(dir.join("Chrome.zip.crdownload"), "data").unwrap();
+        inbox.poll();
+        wait_for_settle(&mut inbox);
+        assert!(inbox.poll().is_empty());
+        assert!(inbox.pending.is_empty());
+
+        fs::write(&firefox, "data").unwrap();
+        fs::remove_file(dir.join("Firefox.zip.part")).unwrap();
+        fs::remove_file(dir.join("Chrome.zip.crdownload")).unwrap();
+        inbox.poll();
+        wait_for_settle(&mut inbox);
+        assert_eq!(inbox.poll(), [chrome, firefox]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
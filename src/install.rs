@@ -0,0 +1,378 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use zip::ZipArchive;
+
+use crate::error::Error;
+use crate::mods::{InstalledMod, ModManifest, ModScan};
+
+/// A mod found inside an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveMod {
+    /// Folder inside the archive holding `manifest.json`, empty for the archive root.
+    pub prefix: String,
+    pub manifest: ModManifest,
+    /// Installed copy with the same UniqueID, which gets upgraded in place.
+    pub existing: Option<InstalledMod>,
+}
+
+impl ArchiveMod {
+    fn destination(&self, plan: &InstallPlan) -> PathBuf {
+        if let Some(existing) = &self.existing {
+            return existing.path.clone();
+        }
+        let folder_name = match self.prefix.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => match plan.archive.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => self.manifest.unique_id.clone(),
+            },
+        };
+        plan.mods_folder.join(folder_name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstallPlan {
+    pub archive: PathBuf,
+    pub mods_folder: PathBuf,
+    pub mods: Vec<ArchiveMod>,
+}
+
+impl InstallPlan {
+    /// Reads every `manifest.json` in `archive`, handling wrapper folders and
+    /// archives bundling several mods, and matches them against `installed`.
+    pub fn read(
+        archive: &Path,
+        mods_folder: &Path,
+        installed: &ModScan,
+    ) -> Result<InstallPlan, Error> {
+        let mut zip = open_archive(archive)?;
+
+        let mut prefixes: Vec<String> = zip
+            .file_names()
+            .filter(|name| !name.starts_with("__MACOSX/"))
+            .filter_map(|name| {
+                if name == "manifest.json" {
+                    Some(String::new())
+                } else {
+                    name.strip_suffix("/manifest.json").map(|p| p.to_string())
+                }
+            })
+            .collect();
+        prefixes.sort();
+
+        // A manifest nested inside another mod's folder isn't loaded by SMAPI.
+        let mut outermost: Vec<String> = Vec::new();
+        for prefix in prefixes {
+            if !outermost.iter().any(|outer| is_inside(&prefix, outer)) {
+                outermost.push(prefix);
+            }
+        }
+        if outermost.is_empty() {
+            return Err(Error::Parse(format!(
+                "{} contains no manifest.json",
+                archive.display()
+            )));
+        }
+
+        let mut mods = Vec::new();
+        for prefix in outermost {
+            let manifest_name = if prefix.is_empty() {
+                "manifest.json".to_string()
+            } else {
+                format!("{prefix}/manifest.json")
+            };
+            let mut contents = String::new();
+            let read = match zip.by_name(manifest_name.as_str()) {
+                Ok(mut file) => file.read_to_string(&mut contents),
+                Err(e) => return Err(Error::Filesystem(e.to_string())),
+            };
+            if let Err(e) = read {
+                return Err(Error::Filesystem(format!("{manifest_name}: {e}")));
+            }
+            let manifest = match ModManifest::parse(contents.as_str()) {
+                Ok(m) => m,
+                Err(e) => return Err(Error::Parse(format!("{manifest_name}: {e}"))),
+            };
+            let existing = installed
+                .mods
+                .iter()
+                .find(|m| {
+                    m.manifest
+                        .unique_id
+                        .eq_ignore_ascii_case(&manifest.unique_id)
+                })
+                .cloned();
+            mods.push(ArchiveMod {
+                prefix,
+                manifest,
+                existing,
+            });
+        }
+
+        Ok(InstallPlan {
+            archive: archive.to_path_buf(),
+            mods_folder: mods_folder.to_path_buf(),
+            mods,
+        })
+    }
+
+    pub fn has_upgrades(&self) -> bool {
+        self.mods.iter().any(|m| m.existing.is_some())
+    }
+
+    /// Extracts every mod of the plan, replacing installed copies with the same
+    /// UniqueID while keeping their `config.json`.
+    pub fn install(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut zip = open_archive(&self.archive)?;
+        if let Err(e) = fs::create_dir_all(&self.mods_folder) {
+            return Err(Error::Filesystem(e.to_string()));
+        }
+
+        let mut installed = Vec::new();
+        for archive_mod in &self.mods {
+            let destination = archive_mod.destination(self);
+            if archive_mod.existing.is_none() && destination.exists() {
+                return Err(Error::Filesystem(format!(
+                    "{} already exists",
+                    destination.display()
+                )));
+            }
+
+            let staging = staging_path(&destination);
+            if staging.exists() {
+                if let Err(e) = fs::remove_dir_all(&staging) {
+                    return Err(Error::Filesystem(e.to_string()));
+                }
+            }
+            if let Err(e) = extract(&mut zip, &archive_mod.prefix, &staging) {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+
+            if archive_mod.existing.is_some() {
+                let config = destination.join("config.json");
+                if config.is_file() {
+                    if let Err(e) = fs::copy(&config, staging.join("config.json")) {
+                        let _ = fs::remove_dir_all(&staging);
+                        return Err(Error::Filesystem(e.to_string()));
+                    }
+                }
+            }
+
+            // the installed copy is only deleted once the new one is in place
+            let replaced = aside_path(&destination);
+            if archive_mod.existing.is_some() {
+                if replaced.exists() {
+                    if let Err(e) = fs::remove_dir_all(&replaced) {
+                        let _ = fs::remove_dir_all(&staging);
+                        return Err(Error::Filesystem(e.to_string()));
+                    }
+                }
+                if let Err(e) = fs::rename(&destination, &replaced) {
+                    let _ = fs::remove_dir_all(&staging);
+                    return Err(Error::Filesystem(e.to_string()));
+                }
+            }
+            if let Err(e) = fs::rename(&staging, &destination) {
+                if archive_mod.existing.is_some() {
+                    let _ = fs::rename(&replaced, &destination);
+                }
+                let _ = fs::remove_dir_all(&staging);
+                return Err(Error::Filesystem(e.to_string()));
+            }
+            if archive_mod.existing.is_some() {
+                // a leftover is dotted, so SMAPI skips it and the next upgrade
+                // clears it
+                let _ = fs::remove_dir_all(&replaced);
+            }
+            installed.push(destination);
+        }
+        Ok(installed)
+    }
+}
+
+fn open_archive(archive: &Path) -> Result<ZipArchive<File>, Error> {
+    let file = match File::open(archive) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::Filesystem(format!("{}: {e}", archive.display()))),
+    };
+    match ZipArchive::new(file) {
+        Ok(zip) => Ok(zip),
+        Err(e) => Err(Error::Filesystem(format!("{}: {e}", archive.display()))),
+    }
+}
+
+fn is_inside(prefix: &str, outer: &str) -> bool {
+    outer.is_empty() || prefix.starts_with(format!("{outer}/").as_str())
+}
+
+/// Dotted so SMAPI ignores a half-extracted mod.
+fn staging_path(destination: &Path) -> PathBuf {
+    dotted_path(destination, "installing")
+}
+
+/// Where the installed copy waits while an upgrade moves into place.
+fn aside_path(destination: &Path) -> PathBuf {
+    dotted_path(destination, "replaced")
+}
+
+fn dotted_path(destination: &Path, suffix: &str) -> PathBuf {
+    let name = match destination.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => "mod".into(),
+    };
+    destination.with_file_name(format!(".{}.{suffix}", name.trim_start_matches('.')))
+}
+
+fn extract(zip: &mut ZipArchive<File>, prefix: &str, target: &Path) -> Result<(), Error> {
+    for i in 0..zip.len() {
+        let mut file = match zip.by_index(i) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::Filesystem(e.to_string())),
+        };
+        let name = match file.enclosed_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        if name.starts_with("__MACOSX") {
+            continue;
+        }
+        let relative = match name.strip_prefix(prefix) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => continue,
+        };
+        let out_path = target.join(relative);
+
+        let result = if file.is_dir() {
+            fs::create_dir_all(&out_path)
+        } else {
+            if let Some(parent) = out_path.parent() {
+                if let Err(e) = fs::create_dir_all(parent) {
+                    return Err(Error::Filesystem(e.to_string()));
+                }
+            }
+            File::create(&out_path).and_then(|mut out| io::copy(&mut file, &mut out).map(|_| ()))
+        };
+        if let Err(e) = result {
+            return Err(Error::Filesystem(format!("{}: {e}", out_path.display())));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::Write};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    /// A fresh folder holding a `mods` folder and the archive `name`.zip with
+    /// `files`, given as path and contents.
+    fn archive(name: &str, files: &[(&str, &str)]) -> (PathBuf, PathBuf) {
+        let dir = env::temp_dir().join(format!(
+            "smapi-instance-manager-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("mods")).unwrap();
+        let path = dir.join(format!("{name}.zip"));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, contents) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        (path, dir.join("mods"))
+    }
+
+    fn manifest(id: &str, version: &str) -> String {
+        format!(r#"{{"Name": "{id}", "Version": "{version}", "UniqueID": "{id}"}}"#)
+    }
+
+    #[test]
+    fn finds_the_outermost_mod_folders() {
+        let (path, mods) = archive(
+            "bundle",
+            &[
+                ("Bundle/ModA/manifest.json", &manifest("a.a", "1.0.0")),
+                ("Bundle/ModA/ModA.dll", ""),
+                (
+                    "Bundle/ModA/assets/manifest.json",
+                    &manifest("a.nested", "1.0.0"),
+                ),
+                ("Bundle/ModB/manifest.json", &manifest("b.b", "1.0.0")),
+                (
+                    "__MACOSX/Bundle/ModC/manifest.json",
+                    &manifest("c.c", "1.0.0"),
+                ),
+            ],
+        );
+        let plan = InstallPlan::read(&path, &mods, &ModScan::default()).unwrap();
+        let prefixes: Vec<&str> = plan.mods.iter().map(|m| m.prefix.as_str()).collect();
+        assert_eq!(prefixes, ["Bundle/ModA", "Bundle/ModB"]);
+        assert!(!plan.has_upgrades());
+
+        let installed = plan.install().unwrap();
+        assert_eq!(installed, [mods.join("ModA"), mods.join("ModB")]);
+        assert!(mods.join("ModA/ModA.dll").is_file());
+        assert!(mods.join("ModA/assets/manifest.json").is_file());
+        let _ = fs::remove_dir_all(mods.parent().unwrap());
+    }
+
+    #[test]
+    fn manifest_at_the_root_installs_into_a_folder_named_after_the_archive() {
+        let (path, mods) = archive("RootMod", &[("manifest.json", &manifest("r.r", "1.0.0"))]);
+        let plan = InstallPlan::read(&path, &mods, &ModScan::default()).unwrap();
+        assert_eq!(plan.install().unwrap(), [mods.join("RootMod")]);
+        assert!(mods.join("RootMod/manifest.json").is_file());
+        let _ = fs::remove_dir_all(mods.parent().unwrap());
+    }
+
+    #[test]
+    fn archive_without_a_manifest_is_rejected() {
+        let (path, mods) = archive("empty", &[("readme.txt", "nothing here")]);
+        let result = InstallPlan::read(&path, &mods, &ModScan::default());
+        assert!(matches!(result, Err(Error::Parse(_))));
+        let _ = fs::remove_dir_all(mods.parent().unwrap());
+    }
+
+    #[test]
+    fn upgrade_keeps_the_config_and_replaces_the_old_copy() {
+        let (path, mods) = archive(
+            "upgrade",
+            &[
+                ("ModA/manifest.json", &manifest("a.a", "2.0.0")),
+                ("ModA/config.json", "default"),
+                ("ModA/new.dll", ""),
+            ],
+        );
+        let old = mods.join("Renamed A");
+        fs::create_dir_all(&old).unwrap();
+        fs::write(old.join("manifest.json"), manifest("A.A", "1.0.0")).unwrap();
+        fs::write(old.join("config.json"), "mine").unwrap();
+        fs::write(old.join("old.dll"), "").unwrap();
+
+        let plan = InstallPlan::read(&path, &mods, &ModScan::scan(&mods)).unwrap();
+        assert!(plan.has_upgrades());
+        assert_eq!(plan.install().unwrap(), std::slice::from_ref(&old));
+
+        let manifest = ModManifest::load(&old.join("manifest.json")).unwrap();
+        assert_eq!(manifest.version, "2.0.0");
+        assert_eq!(fs::read_to_string(old.join("config.json")).unwrap(), "mine");
+        assert!(old.join("new.dll").is_file());
+        assert!(!old.join("old.dll").exists());
+        let leftovers: Vec<PathBuf> = fs::read_dir(&mods)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(leftovers, [old]);
+        let _ = fs::remove_dir_all(mods.parent().unwrap());
+    }
+}
@@ -0,0 +1,136 @@
+use std::{env, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchMode {
+    /// Opens SMAPI in a terminal emulator.
+    #[default]
+    Terminal,
+    /// Runs SMAPI directly and shows its output in the manager.
+    Headless,
+}
+
+/// How an instance is started. Both parts are split on whitespace before the
+/// placeholders are filled in, so paths with spaces stay a single argument.
+///
+/// Placeholders: `{smapi}` (the `StardewModdingAPI` executable), `{mods}` (the
+/// mods folder), `{instance}` (the instance name) and `{terminal}` (`$TERMINAL`,
+/// or `konsole` if it isn't set).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LaunchTemplate {
+    /// Opens a terminal that runs the words following it.
+    pub terminal: String,
+    /// Runs SMAPI inside that terminal.
+    pub command: String,
+    #[serde(default)]
+    pub mode: LaunchMode,
+}
+
+impl Default for LaunchTemplate {
+    fn default() -> LaunchTemplate {
+        LaunchTemplate {
+            terminal: TERMINAL_PRESETS[0].1.into(),
+            command: WRAPPER_PRESETS[1].1.into(),
+            mode: LaunchMode::Terminal,
+        }
+    }
+}
+
+/// Terminals and the flag that makes them run a command.
+pub const TERMINAL_PRESETS: [(&str, &str); 7] = [
+    ("$TERMINAL", "{terminal} -e"),
+    ("kitty", "kitty --"),
+    ("alacritty", "alacritty -e"),
+    ("gnome-terminal", "gnome-terminal --"),
+    ("wezterm", "wezterm start --"),
+    ("foot", "foot"),
+    ("konsole", "konsole -e"),
+];
+
+/// Programs SMAPI can be wrapped in.
+pub const WRAPPER_PRESETS: [(&str, &str); 4] = [
+    ("none", "{smapi} --mods-path {mods}"),
+    ("steam-run", "steam-run {smapi} --mods-path {mods}"),
+    ("gamemoderun", "gamemoderun {smapi} --mods-path {mods}"),
+    ("mangohud", "mangohud {smapi} --mods-path {mods}"),
+];
+
+/// Values for the placeholders of a template.
+pub struct LaunchContext {
+    pub smapi: String,
+    pub mods: String,
+    pub instance: String,
+}
+
+impl LaunchContext {
+    fn fill(&self, template: &str) -> Vec<String> {
+        let terminal = env::var("TERMINAL").unwrap_or("konsole".into());
+        template
+            .split_whitespace()
+            .map(|word| {
+                word.replace("{smapi}", &self.smapi)
+                    .replace("{mods}", &self.mods)
+                    .replace("{instance}", &self.instance)
+                    .replace("{terminal}", &terminal)
+            })
+            .collect()
+    }
+}
+
+impl LaunchTemplate {
+    /// The SMAPI command on its own, without a terminal around it.
+    pub fn command_args(&self, context: &LaunchContext) -> Vec<String> {
+        context.fill(&self.command)
+    }
+
+    /// The terminal followed by the SMAPI command.
+    pub fn terminal_args(&self, context: &LaunchContext) -> Vec<String> {
+        let mut args = context.fill(&self.terminal);
+        args.extend(self.command_args(context));
+        args
+    }
+
+    /// Everything needed to start SMAPI in this template's mode.
+    pub fn args(&self, context: &LaunchContext) -> Vec<String> {
+        match self.mode {
+            LaunchMode::Terminal => self.terminal_args(context),
+            LaunchMode::Headless => self.command_args(context),
+        }
+    }
+
+    /// Builds the process for `args`. In headless mode SMAPI's launcher is
+    /// told to stay in the current shell, as it would otherwise try to open a
+    /// terminal of its own. `SMAPI_NO_TERMINAL` would also turn off the
+    /// console output the manager captures.
+    pub fn command(&self, context: &LaunchContext) -> Result<Command, Error> {
+        let args = self.args(context);
+        let (program, args) = match args.split_first() {
+            Some(split) => split,
+            None => return Err(Error::Launch("the launch template is empty".into())),
+        };
+        let mut command = Command::new(program);
+        command.args(args);
+        if let LaunchMode::Headless = self.mode {
+            command.env("SMAPI_USE_CURRENT_SHELL", "true");
+        }
+        Ok(command)
+    }
+
+    /// Index of the terminal preset this template uses, if any.
+    pub fn terminal_preset(&self) -> Option<usize> {
+        TERMINAL_PRESETS
+            .iter()
+            .position(|(_, terminal)| *terminal == self.terminal)
+    }
+
+    /// Index of the wrapper preset this template uses, if any.
+    pub fn wrapper_preset(&self) -> Option<usize> {
+        WRAPPER_PRESETS
+            .iter()
+            .position(|(_, command)| *command == self.command)
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Written into a mods folder while SMAPI runs from it, so a second launch
+/// from the same folder, also by another manager, can be caught.
+pub const LOCK_FILE: &str = ".smapi-instance-manager.lock";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lock {
+    pub pid: u32,
+    pub instance: String,
+}
+
+fn lock_path(mods: &Path) -> PathBuf {
+    mods.join(LOCK_FILE)
+}
+
+/// Whether a process with this id exists.
+pub fn is_alive(pid: u32) -> bool {
+    Path::new(&format!("/proc/{pid}")).exists()
+}
+
+impl Lock {
+    /// The lock on `mods` if the process holding it still runs. Locks left
+    /// behind by a crash are removed.
+    pub fn read(mods: &Path) -> Option<Lock> {
+        let path = lock_path(mods);
+        let contents = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<Lock>(contents.as_str()) {
+            Ok(lock) if is_alive(lock.pid) => Some(lock),
+            _ => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    pub fn write(mods: &Path, pid: u32, instance: String) -> Result<(), Error> {
+        let path = lock_path(mods);
+        let contents = match serde_json::to_string(&Lock { pid, instance }) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::Parse(e.to_string())),
+        };
+        match fs::write(&path, contents) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Filesystem(format!("{}: {e}", path.display()))),
+        }
+    }
+
+    /// Removes the lock on `mods` if it still belongs to `pid`.
+    pub fn release(mods: &Path, pid: u32) {
+        let path = lock_path(mods);
+        if let Ok(contents) = fs::read_to_string(&path) {
+            if let Ok(lock) = serde_json::from_str::<Lock>(contents.as_str()) {
+                if lock.pid == pid {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+    }
+}
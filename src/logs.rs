@@ -0,0 +1,290 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Local};
+
+use crate::detect::game_data_dir;
+use crate::error::Error;
+
+/// Folder inside an instance's mods folder that keeps the logs of its sessions.
+pub const LOG_DIR: &str = ".logs";
+
+/// Severity SMAPI prints in front of every message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Alert,
+}
+
+impl LogLevel {
+    pub fn parse(level: &str) -> Option<LogLevel> {
+        match level {
+            "TRACE" => Some(LogLevel::Trace),
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" => Some(LogLevel::Warn),
+            "ERROR" => Some(LogLevel::Error),
+            "ALERT" => Some(LogLevel::Alert),
+            _ => None,
+        }
+    }
+
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::Trace,
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+        LogLevel::Alert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Alert => "ALERT",
+        }
+    }
+
+    /// Level of a line like `[12:34:56 WARN  SMAPI] message`, or `None` for
+    /// the continuation lines of multi-line messages.
+    pub fn of_line(line: &str) -> Option<LogLevel> {
+        Some(parse_header(line)?.1)
+    }
+}
+
+/// Splits `[12:34:56 WARN  Content Patcher] message` into its time, level,
+/// source and message.
+fn parse_header(line: &str) -> Option<(&str, LogLevel, &str, &str)> {
+    let (header, message) = line.strip_prefix('[')?.split_once(']')?;
+    let (time, rest) = header.trim_start().split_once(' ')?;
+    let rest = rest.trim_start();
+    let (level, source) = rest.split_once(' ').unwrap_or((rest, ""));
+    Some((
+        time,
+        LogLevel::parse(level)?,
+        source.trim(),
+        message.strip_prefix(' ').unwrap_or(message),
+    ))
+}
+
+/// A line of SMAPI's console output.
+pub struct ConsoleLine {
+    pub level: LogLevel,
+    pub text: String,
+}
+
+impl ConsoleLine {
+    /// Lines without a header keep the level of the message they continue.
+    pub fn new(text: String, previous: Option<&ConsoleLine>) -> ConsoleLine {
+        let level = match LogLevel::of_line(&text) {
+            Some(level) => level,
+            None => match previous {
+                Some(previous) => previous.level,
+                None => LogLevel::Info,
+            },
+        };
+        ConsoleLine { level, text }
+    }
+}
+
+/// A message of a SMAPI log file. Messages can span several lines.
+pub struct LogEntry {
+    pub time: String,
+    pub level: LogLevel,
+    pub source: String,
+    pub message: String,
+}
+
+pub struct SmapiLog {
+    pub path: PathBuf,
+    pub entries: Vec<LogEntry>,
+}
+
+impl SmapiLog {
+    /// Lines before the first header, like the log's title, are dropped.
+    pub fn parse(path: PathBuf, contents: &str) -> SmapiLog {
+        let mut entries: Vec<LogEntry> = Vec::new();
+        for line in contents.lines() {
+            match parse_header(line) {
+                Some((time, level, source, message)) => entries.push(LogEntry {
+                    time: time.to_string(),
+                    level,
+                    source: source.to_string(),
+                    message: message.to_string(),
+                }),
+                None => {
+                    if let Some(entry) = entries.last_mut() {
+                        entry.message.push('\n');
+                        entry.message.push_str(line);
+                    }
+                }
+            }
+        }
+        SmapiLog { path, entries }
+    }
+
+    pub fn load(path: &Path) -> Result<SmapiLog, Error> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(SmapiLog::parse(path.to_path_buf(), contents.as_str())),
+            Err(e) => Err(Error::Filesystem(format!("{}: {e}", path.display()))),
+        }
+    }
+
+    /// Every mod or component that wrote to the log, sorted by name.
+    pub fn sources(&self) -> Vec<String> {
+        let mut sources: Vec<String> = self.entries.iter().map(|e| e.source.clone()).collect();
+        sources.sort();
+        sources.dedup();
+        sources
+    }
+
+    pub fn filtered<'a>(
+        &'a self,
+        min_level: LogLevel,
+        source: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a LogEntry> {
+        self.entries.iter().filter(move |entry| {
+            entry.level >= min_level
+                && match source {
+                    Some(source) => entry.source == source,
+                    None => true,
+                }
+        })
+    }
+}
+
+/// SMAPI overwrites this file on every launch, whichever mods folder is used.
+pub fn latest_log_path() -> PathBuf {
+    game_data_dir().join("ErrorLogs").join("SMAPI-latest.txt")
+}
+
+/// Copies `SMAPI-latest.txt` into the `.logs` folder of `mods` if it was
+/// written after `started`. Returns where the copy went.
+pub fn archive_latest(mods: &Path, started: SystemTime) -> Result<Option<PathBuf>, Error> {
+    let latest = latest_log_path();
+    let modified = match fs::metadata(&latest).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified,
+        Err(_) => return Ok(None),
+    };
+    if modified < started {
+        return Ok(None);
+    }
+
+    let dir = mods.join(LOG_DIR);
+    if let Err(e) = fs::create_dir_all(&dir) {
+        return Err(Error::Filesystem(format!("{}: {e}", dir.display())));
+    }
+    let started: DateTime<Local> = started.into();
+    let archive = dir.join(format!("SMAPI-{}.txt", started.format("%Y-%m-%d_%H-%M-%S")));
+    match fs::copy(&latest, &archive) {
+        Ok(_) => Ok(Some(archive)),
+        Err(e) => Err(Error::Filesystem(format!("{}: {e}", archive.display()))),
+    }
+}
+
+/// Archived logs of the instance using `mods`, newest first.
+pub fn archived_logs(mods: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(mods.join(LOG_DIR)) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| match path.file_name() {
+            Some(name) => {
+                let name = name.to_string_lossy();
+                name.starts_with("SMAPI-") && name.ends_with(".txt")
+            }
+            None => false,
+        })
+        .collect();
+    // the timestamp in the name sorts chronologically
+    logs.sort();
+    logs.reverse();
+    logs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+SMAPI log
+[12:00:01 INFO  SMAPI] SMAPI 4.0.8 with Stardew Valley 1.6.8
+[12:00:02 TRACE SMAPI] Loading mods...
+[12:00:03 WARN  Content Patcher] Couldn't load content pack:
+   missing file assets/a.png
+   in pack Example
+[12:00:04 ERROR Lookup Anything] Failed to read data.
+[12:00:05 DEBUG Content Patcher] Applied 3 patches.
+";
+
+    fn log() -> SmapiLog {
+        SmapiLog::parse(PathBuf::from("SMAPI-latest.txt"), LOG)
+    }
+
+    #[test]
+    fn header_is_split_into_its_parts() {
+        assert_eq!(
+            parse_header("[12:34:56 WARN  Content Patcher] a message"),
+            Some(("12:34:56", LogLevel::Warn, "Content Patcher", "a message"))
+        );
+        assert_eq!(
+            LogLevel::of_line("[12:34:56 ALERT SMAPI] update"),
+            Some(LogLevel::Alert)
+        );
+        assert_eq!(LogLevel::of_line("   at Method()"), None);
+        assert_eq!(LogLevel::of_line("[12:34:56 LOUD  SMAPI] x"), None);
+    }
+
+    #[test]
+    fn continuation_lines_belong_to_the_previous_message() {
+        let log = log();
+        assert_eq!(log.entries.len(), 5);
+        let warning = &log.entries[2];
+        assert_eq!(warning.time, "12:00:03");
+        assert_eq!(warning.level, LogLevel::Warn);
+        assert_eq!(
+            warning.message,
+            "Couldn't load content pack:\n   missing file assets/a.png\n   in pack Example"
+        );
+
+        let first = ConsoleLine::new("[12:00:03 ERROR SMAPI] x".to_string(), None);
+        let next = ConsoleLine::new("   at Method()".to_string(), Some(&first));
+        assert_eq!(next.level, LogLevel::Error);
+    }
+
+    #[test]
+    fn filters_by_level_and_mod() {
+        let log = log();
+        assert_eq!(
+            log.sources(),
+            ["Content Patcher", "Lookup Anything", "SMAPI"]
+        );
+
+        let times = |min_level, source| -> Vec<&str> {
+            log.filtered(min_level, source)
+                .map(|entry| entry.time.as_str())
+                .collect()
+        };
+        assert_eq!(times(LogLevel::Trace, None).len(), 5);
+        assert_eq!(times(LogLevel::Warn, None), ["12:00:03", "12:00:04"]);
+        assert_eq!(
+            times(LogLevel::Trace, Some("Content Patcher")),
+            ["12:00:03", "12:00:05"]
+        );
+        assert_eq!(times(LogLevel::Info, Some("SMAPI")), ["12:00:01"]);
+    }
+}
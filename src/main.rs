@@ -1,4 +1,5 @@
 use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::crossterm::cursor::Show;
 use ratatui::crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode};
 use ratatui::crossterm::execute;
 use ratatui::crossterm::terminal::{
@@ -7,67 +8,190 @@ use ratatui::crossterm::terminal::{
 use ratatui::Terminal;
 use std::error::Error;
 use std::io;
+use std::panic;
+use std::process;
+use std::time::Duration;
 
 mod app;
+mod backup;
+mod checks;
+mod cli;
+mod config;
+mod detect;
+mod error;
+mod files;
+mod inbox;
+mod install;
+mod launch;
+mod lock;
+mod logs;
+mod mods;
+mod output;
+mod saves;
+mod session;
+mod summary;
 mod ui;
 
-use app::{App, CurrentScreen, CurrentlyAdding};
-use ui::ui;
+use app::{expand_home, App, CurrentScreen, CurrentlyAdding, Focus};
+use clap::Parser;
+use cli::Cli;
+use ui::{progress, ui};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let config_path = match cli.config {
+        Some(path) => path,
+        None => {
+            let path = config::default_config_path();
+            match config::migrate_legacy_config(&path) {
+                Ok(true) => eprintln!("Moved ./config.json to {}", path.display()),
+                Ok(false) => (),
+                Err(e) => eprintln!("Failed to move ./config.json: {e}"),
+            }
+            path
+        }
+    };
+
+    if let Some(command) = cli.command {
+        if let Err(e) = cli::run(command, cli.json, &config_path) {
+            eprintln!("Error: {e}");
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
     // create app before terminal is unsuable
-    let mut app = App::new();
-    app.manager.save();
+    let mut app = match App::new(config_path) {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("Error: failed to load config: {e}");
+            process::exit(1);
+        }
+    };
+    if let CurrentScreen::Main = app.screen {
+        if let Err(e) = app.manager.save() {
+            eprintln!("Error: failed to save config: {e}");
+            process::exit(1);
+        }
+    }
+
+    // leave raw mode before the panic message is printed
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
 
     // setup terminal
+    let res = match setup_terminal() {
+        Ok(mut terminal) => run_app(&mut terminal, &mut app),
+        Err(e) => Err(e),
+    };
+    let stopped = app.stop_headless();
+
+    restore_terminal()?;
+
+    if let Err(err) = res {
+        println!("{err:?}");
+    }
+    if let Err(err) = stopped {
+        println!("failed to restore the shared saves: {err}");
+    }
+
+    Ok(())
+}
+
+fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
 
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    let res = run_app(&mut terminal, &mut app);
+    Terminal::new(backend)
+}
 
+/// Safe to call more than once, also when the terminal was never set up.
+fn restore_terminal() -> io::Result<()> {
     disable_raw_mode()?;
     execute!(
-        terminal.backend_mut(),
+        io::stdout(),
         LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-
-    if let Err(err) = res {
-        println!("{err:?}");
-    }
-
-    Ok(())
+        DisableMouseCapture,
+        Show
+    )
 }
 
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
     loop {
+        // also runs between key presses so finished launches show up right away
+        app.tick();
         terminal.draw(|f| ui(f, app))?;
 
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+
         if let Event::Key(key) = event::read()? {
             if key.kind != event::KeyEventKind::Press {
                 continue;
             }
 
+            if app.error.is_some() {
+                app.error = None;
+                continue;
+            }
+
             match app.screen {
+                CurrentScreen::Setup => match key.code {
+                    KeyCode::Up => app.select_prev_candidate(),
+                    KeyCode::Down => app.select_next_candidate(),
+                    KeyCode::Enter => app.finish_setup(),
+                    KeyCode::Backspace => {
+                        app.path_input.pop();
+                    }
+                    // nothing to go back to before there is a config
+                    KeyCode::Esc => return Ok(()),
+                    KeyCode::Char(value) => {
+                        app.path_input.push(value);
+                    }
+                    _ => (),
+                },
                 CurrentScreen::Main => match key.code {
-                    KeyCode::Up => {
-                        app.select_prev();
+                    KeyCode::Up => match app.focus {
+                        Focus::Instances => app.select_prev(),
+                        Focus::Mods => app.select_prev_mod(),
+                    },
+                    KeyCode::Down => match app.focus {
+                        Focus::Instances => app.select_next(),
+                        Focus::Mods => app.select_next_mod(),
+                    },
+                    KeyCode::Tab => {
+                        app.toggle_focus();
+                    }
+                    KeyCode::Char(' ') => {
+                        if let Focus::Mods = app.focus {
+                            app.toggle_selected_mod();
+                        }
+                    }
+                    KeyCode::Enter => app.start_instance(false),
+                    KeyCode::Char('v') => {
+                        app.run_checks();
+                        app.screen = CurrentScreen::Checks;
+                    }
+                    KeyCode::Char('i') => {
+                        app.screen = CurrentScreen::Install;
+                    }
+                    KeyCode::Char('w') => {
+                        app.path_input = app.manager.inbox.clone().unwrap_or_default();
+                        app.screen = CurrentScreen::SetInbox;
+                    }
+                    KeyCode::Char('e') => {
+                        app.start_edit();
+                    }
+                    KeyCode::Char('c') => {
+                        app.screen = CurrentScreen::Clone;
+                        app.adding = Some(CurrentlyAdding::Name);
                     }
-                    KeyCode::Down => {
-                        app.select_next();
-                    }
-                    KeyCode::Enter => app.manager.run(
-                        app.manager
-                            .instances
-                            .get(&app.current_instance)
-                            .unwrap()
-                            .clone(),
-                    ),
                     KeyCode::Char('a') => {
                         app.screen = CurrentScreen::Add;
                         app.adding = Some(CurrentlyAdding::Name);
@@ -79,22 +203,35 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                         app.screen = CurrentScreen::Remove;
                     }
                     KeyCode::Char('o') => {
-                        app.manager.open(
-                            app.manager
-                                .instances
-                                .get(&app.current_instance)
-                                .unwrap()
-                                .clone(),
-                        );
+                        app.open_current();
+                    }
+                    KeyCode::Char('l') => {
+                        app.start_launch_settings();
+                    }
+                    KeyCode::Char('h') => {
+                        app.console_scroll = None;
+                        app.screen = CurrentScreen::Console;
+                    }
+                    KeyCode::Char('g') => {
+                        app.open_logs();
+                    }
+                    KeyCode::Char('p') => {
+                        app.open_history();
+                    }
+                    KeyCode::Char('s') => {
+                        app.open_saves();
+                    }
+                    KeyCode::Char('k') if app.is_running(&app.current_instance) => {
+                        app.screen = CurrentScreen::Stop;
                     }
                     _ => {}
                 },
-                CurrentScreen::Add => match key.code {
+                CurrentScreen::Add | CurrentScreen::Edit => match key.code {
                     KeyCode::Enter => {
                         if let Some(adding) = &app.adding {
                             match adding {
                                 CurrentlyAdding::Name => {
-                                    if app.manager.instances.contains_key(&app.name_input) {
+                                    if app.name_is_taken() {
                                         continue;
                                     }
                                     app.adding = Some(CurrentlyAdding::FolderName);
@@ -103,6 +240,16 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                     app.adding = Some(CurrentlyAdding::SmapiPath);
                                 }
                                 CurrentlyAdding::SmapiPath => {
+                                    app.adding = Some(CurrentlyAdding::SavesDir);
+                                }
+                                CurrentlyAdding::SavesDir => {
+                                    if app.name_is_taken() {
+                                        continue;
+                                    }
+                                    if let CurrentScreen::Edit = app.screen {
+                                        app.submit_edit();
+                                        continue;
+                                    }
                                     app.save_instance();
                                     app.adding = None;
                                     app.screen = CurrentScreen::Main;
@@ -122,6 +269,9 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                 CurrentlyAdding::SmapiPath => {
                                     app.smapi_path_input.pop();
                                 }
+                                CurrentlyAdding::SavesDir => {
+                                    app.saves_dir_input.pop();
+                                }
                             }
                         }
                     }
@@ -130,7 +280,7 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                         app.adding = None;
                     }
                     KeyCode::Tab => {
-                        if app.manager.instances.contains_key(&app.name_input) {
+                        if app.name_is_taken() {
                             continue;
                         }
                         app.toggle_adding();
@@ -147,11 +297,266 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                                 CurrentlyAdding::SmapiPath => {
                                     app.smapi_path_input.push(value);
                                 }
+                                CurrentlyAdding::SavesDir => {
+                                    app.saves_dir_input.push(value);
+                                }
                             }
                         }
                     }
                     _ => (),
                 },
+                CurrentScreen::Clone => match key.code {
+                    KeyCode::Enter | KeyCode::Tab => {
+                        if app.name_is_taken() {
+                            continue;
+                        }
+                        match app.adding {
+                            Some(CurrentlyAdding::Name) => {
+                                app.adding = Some(CurrentlyAdding::FolderName);
+                            }
+                            _ if key.code == KeyCode::Tab => {
+                                app.adding = Some(CurrentlyAdding::Name);
+                            }
+                            _ => {
+                                if app.name_input.is_empty() || app.folder_name_input.is_empty() {
+                                    continue;
+                                }
+                                app.screen = CurrentScreen::CloneMode;
+                            }
+                        }
+                    }
+                    KeyCode::Backspace => match app.adding {
+                        Some(CurrentlyAdding::Name) => {
+                            app.name_input.pop();
+                        }
+                        _ => {
+                            app.folder_name_input.pop();
+                        }
+                    },
+                    KeyCode::Esc => {
+                        app.screen = CurrentScreen::Main;
+                        app.adding = None;
+                    }
+                    KeyCode::Char(value) => match app.adding {
+                        Some(CurrentlyAdding::Name) => app.name_input.push(value),
+                        _ => app.folder_name_input.push(value),
+                    },
+                    _ => (),
+                },
+                CurrentScreen::CloneMode => match key.code {
+                    KeyCode::Char('f') | KeyCode::Char('c') => {
+                        let configs_only = key.code == KeyCode::Char('c');
+                        let mut last_percent = None;
+                        let result = app.manager.clone_instance(
+                            app.current_instance.clone(),
+                            app.name_input.clone(),
+                            app.folder_name_input.clone(),
+                            configs_only,
+                            &mut |done, total| {
+                                let percent = (done * 100).checked_div(total).unwrap_or(100);
+                                if last_percent != Some(percent) {
+                                    last_percent = Some(percent);
+                                    let _ = terminal.draw(|f| progress(f, "Cloning", done, total));
+                                }
+                            },
+                        );
+                        app.finish_clone(result);
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.screen = CurrentScreen::Clone;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::EditRenameFolder => match key.code {
+                    KeyCode::Char('y') => app.finish_edit(true),
+                    KeyCode::Char('n') => app.finish_edit(false),
+                    KeyCode::Esc => {
+                        app.screen = CurrentScreen::Edit;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::LaunchSettings => match key.code {
+                    KeyCode::Up => {
+                        app.launch_row = (app.launch_row + 2) % 3;
+                    }
+                    KeyCode::Down => {
+                        app.launch_row = (app.launch_row + 1) % 3;
+                    }
+                    KeyCode::Left => app.cycle_launch_preset(false),
+                    KeyCode::Right => app.cycle_launch_preset(true),
+                    KeyCode::Char('g') => app.toggle_launch_scope(),
+                    KeyCode::Char('x') => app.reset_launch_override(),
+                    KeyCode::Enter => app.save_launch_settings(),
+                    KeyCode::Esc => {
+                        app.screen = CurrentScreen::Main;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::Console => {
+                    let height = ui::viewer_height(terminal.size()?);
+                    match key.code {
+                        KeyCode::Up => app.scroll_console(-1, height),
+                        KeyCode::Down => app.scroll_console(1, height),
+                        KeyCode::PageUp => app.scroll_console(-(height as isize), height),
+                        KeyCode::PageDown => app.scroll_console(height as isize, height),
+                        KeyCode::Home => app.console_scroll = Some(0),
+                        KeyCode::End => app.console_scroll = None,
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('h') => {
+                            app.screen = CurrentScreen::Main;
+                        }
+                        _ => (),
+                    }
+                }
+                CurrentScreen::Checks => match key.code {
+                    KeyCode::Up => {
+                        app.report_scroll = app.report_scroll.saturating_sub(1);
+                    }
+                    KeyCode::Down => {
+                        app.report_scroll = app.report_scroll.saturating_add(1);
+                    }
+                    KeyCode::Char('y') if app.launch_pending => {
+                        app.start_instance(true);
+                    }
+                    KeyCode::Tab => {
+                        app.select_next_duplicate();
+                    }
+                    KeyCode::Char('d') => {
+                        app.resolve_duplicate(false);
+                    }
+                    KeyCode::Char('x') if !app.report.duplicates.is_empty() => {
+                        app.screen = CurrentScreen::DeleteDuplicate;
+                    }
+                    KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('q') => {
+                        app.launch_pending = false;
+                        app.screen = CurrentScreen::Main;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::DeleteDuplicate => match key.code {
+                    KeyCode::Char('y') => {
+                        app.screen = CurrentScreen::Checks;
+                        app.resolve_duplicate(true);
+                    }
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.screen = CurrentScreen::Checks;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::Install => match key.code {
+                    KeyCode::Enter => {
+                        let archive = expand_home(app.path_input.trim());
+                        app.path_input = String::new();
+                        app.prepare_install(archive);
+                    }
+                    KeyCode::Backspace => {
+                        app.path_input.pop();
+                    }
+                    KeyCode::Esc => {
+                        app.path_input = String::new();
+                        app.screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Char(value) => {
+                        app.path_input.push(value);
+                    }
+                    _ => (),
+                },
+                CurrentScreen::ConfirmInstall => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => app.finish_install(),
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.install_plan = None;
+                        app.screen = CurrentScreen::Main;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::InboxPrompt => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => app.answer_inbox(true),
+                    KeyCode::Char('n') | KeyCode::Esc => app.answer_inbox(false),
+                    _ => (),
+                },
+                CurrentScreen::SetInbox => match key.code {
+                    KeyCode::Enter => app.set_inbox(),
+                    KeyCode::Backspace => {
+                        app.path_input.pop();
+                    }
+                    KeyCode::Esc => {
+                        app.path_input = String::new();
+                        app.screen = CurrentScreen::Main;
+                    }
+                    KeyCode::Char(value) => {
+                        app.path_input.push(value);
+                    }
+                    _ => (),
+                },
+                CurrentScreen::Logs => {
+                    let height = ui::viewer_height(terminal.size()?);
+                    match key.code {
+                        KeyCode::Up => app.scroll_log(-1, height),
+                        KeyCode::Down => app.scroll_log(1, height),
+                        KeyCode::PageUp => app.scroll_log(-(height as isize), height),
+                        KeyCode::PageDown => app.scroll_log(height as isize, height),
+                        KeyCode::Left => app.step_log(-1),
+                        KeyCode::Right => app.step_log(1),
+                        KeyCode::Char('l') => app.cycle_log_level(),
+                        KeyCode::Char('m') => app.cycle_log_source(),
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('g') => {
+                            app.screen = CurrentScreen::Main;
+                        }
+                        _ => (),
+                    }
+                }
+                CurrentScreen::Summary => match key.code {
+                    KeyCode::Left => app.step_history(-1),
+                    KeyCode::Right => app.step_history(1),
+                    KeyCode::Char('g') => app.open_summary_log(),
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') | KeyCode::Char('p') => {
+                        app.screen = CurrentScreen::Main;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::Saves => match key.code {
+                    KeyCode::Down => app.select_next_save(),
+                    KeyCode::Up => app.select_prev_save(),
+                    KeyCode::Enter => app.select_save_instance(),
+                    KeyCode::Char('b') => app.open_backups(),
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('s') => {
+                        app.screen = CurrentScreen::Main;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::Backups => match key.code {
+                    KeyCode::Down => app.select_next_backup(),
+                    KeyCode::Up => app.select_prev_backup(),
+                    KeyCode::Enter => {
+                        app.screen = CurrentScreen::RestoreBackup;
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('b') => {
+                        app.screen = CurrentScreen::Saves;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::RestoreBackup => match key.code {
+                    KeyCode::Char('y') => app.restore_backup(),
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.screen = CurrentScreen::Backups;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::Stop => match key.code {
+                    KeyCode::Char('y') => app.stop_current(),
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.screen = CurrentScreen::Main;
+                    }
+                    _ => (),
+                },
+                CurrentScreen::LockConflict => match key.code {
+                    KeyCode::Char('k') => app.stop_conflicting(),
+                    KeyCode::Char('y') => app.launch_current(),
+                    KeyCode::Char('n') | KeyCode::Esc => {
+                        app.lock_conflict = None;
+                        app.screen = CurrentScreen::Main;
+                    }
+                    _ => (),
+                },
                 CurrentScreen::Exit => match key.code {
                     KeyCode::Char('y') | KeyCode::Char('q') => return Ok(()),
                     KeyCode::Char('n') => {
@@ -161,18 +566,10 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
                 },
                 CurrentScreen::Remove => match key.code {
                     KeyCode::Char('y') => {
-                        // TODO: Delete without folder
-                        let to_delete: String = app.current_instance.clone();
-                        app.select_next();
-                        app.manager.remove_instance(to_delete, false);
-                        app.screen = CurrentScreen::Main;
+                        app.remove_current(false);
                     }
                     KeyCode::Char('a') => {
-                        // TODO: Delete with folder
-                        let to_delete: String = app.current_instance.clone();
-                        app.select_next();
-                        app.manager.remove_instance(to_delete, true);
-                        app.screen = CurrentScreen::Main;
+                        app.remove_current(true);
                     }
                     KeyCode::Char('n') => {
                         app.screen = CurrentScreen::Main;
@@ -0,0 +1,324 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Error;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContentPackFor {
+    #[serde(rename = "UniqueID", alias = "UniqueId")]
+    pub unique_id: String,
+    #[serde(rename = "MinimumVersion", default)]
+    pub minimum_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Dependency {
+    #[serde(rename = "UniqueID", alias = "UniqueId")]
+    pub unique_id: String,
+    #[serde(rename = "MinimumVersion", default)]
+    pub minimum_version: Option<String>,
+    #[serde(rename = "IsRequired", default = "default_required")]
+    pub is_required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModManifest {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Author", default)]
+    pub author: String,
+    #[serde(rename = "Version")]
+    pub version: String,
+    #[serde(rename = "Description", default)]
+    pub description: String,
+    #[serde(rename = "UniqueID", alias = "UniqueId")]
+    pub unique_id: String,
+    #[serde(rename = "EntryDll", default)]
+    pub entry_dll: Option<String>,
+    #[serde(rename = "ContentPackFor", default)]
+    pub content_pack_for: Option<ContentPackFor>,
+    #[serde(rename = "MinimumApiVersion", default)]
+    pub minimum_api_version: Option<String>,
+    #[serde(rename = "Dependencies", default)]
+    pub dependencies: Vec<Dependency>,
+    #[serde(rename = "UpdateKeys", default)]
+    pub update_keys: Vec<String>,
+}
+
+/// Manifest fields, which SMAPI matches ignoring case.
+const MANIFEST_FIELDS: [&str; 12] = [
+    "Name",
+    "Author",
+    "Version",
+    "Description",
+    "UniqueID",
+    "EntryDll",
+    "ContentPackFor",
+    "MinimumApiVersion",
+    "MinimumVersion",
+    "Dependencies",
+    "UpdateKeys",
+    "IsRequired",
+];
+
+/// Renames the keys of `value` and the objects in it that match a manifest
+/// field in another case to the field's own spelling.
+fn match_field_case(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let keys: Vec<String> = map.keys().cloned().collect();
+            for key in keys {
+                let field = MANIFEST_FIELDS
+                    .iter()
+                    .find(|field| field.eq_ignore_ascii_case(&key) && **field != key);
+                if let Some(field) = field {
+                    if let Some(entry) = map.remove(&key) {
+                        map.insert(field.to_string(), entry);
+                    }
+                }
+            }
+            map.values_mut().for_each(match_field_case);
+        }
+        Value::Array(items) => items.iter_mut().for_each(match_field_case),
+        _ => (),
+    }
+}
+
+impl ModManifest {
+    /// Parses a manifest the way SMAPI does, allowing comments, trailing commas,
+    /// a byte order mark and field names in any case.
+    pub fn parse(contents: &str) -> Result<ModManifest, String> {
+        let contents = contents.trim_start_matches('\u{feff}');
+        let mut value: Value = match json5::from_str(contents) {
+            Ok(value) => value,
+            Err(e) => return Err(e.to_string()),
+        };
+        match_field_case(&mut value);
+        match serde_json::from_value(value) {
+            Ok(m) => Ok(m),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<ModManifest, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(e.to_string()),
+        };
+        ModManifest::parse(contents.as_str())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct InstalledMod {
+    pub path: PathBuf,
+    pub manifest: ModManifest,
+    /// SMAPI skips mod folders whose name starts with a dot.
+    pub enabled: bool,
+}
+
+impl InstalledMod {
+    /// Renames the mod folder to or from its dotted form and returns the new path.
+    pub fn set_enabled(&self, enabled: bool) -> Result<PathBuf, Error> {
+        if self.enabled == enabled {
+            return Ok(self.path.clone());
+        }
+        let folder_name = match self.path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => {
+                return Err(Error::MissingPath(format!(
+                    "{} has no folder name",
+                    self.path.display()
+                )))
+            }
+        };
+        let new_name = if enabled {
+            folder_name.trim_start_matches('.').to_string()
+        } else {
+            format!(".{folder_name}")
+        };
+        let new_path = self.path.with_file_name(new_name);
+        if new_path.exists() {
+            return Err(Error::Filesystem(format!(
+                "{} already exists",
+                new_path.display()
+            )));
+        }
+        match fs::rename(&self.path, &new_path) {
+            Ok(_) => Ok(new_path),
+            Err(e) => Err(Error::Filesystem(format!("{}: {e}", self.path.display()))),
+        }
+    }
+
+    pub fn delete(&self) -> Result<(), Error> {
+        match fs::remove_dir_all(&self.path) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Filesystem(format!("{}: {e}", self.path.display()))),
+        }
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().starts_with('.'),
+        None => false,
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModError {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ModScan {
+    pub mods: Vec<InstalledMod>,
+    pub errors: Vec<ModError>,
+}
+
+impl ModScan {
+    /// Walks `root` and every nested folder, treating each folder with a
+    /// `manifest.json` as a mod. Folders inside a mod are not searched further,
+    /// and dotted folders are only picked up when they are a disabled mod.
+    /// Symlinked folders are followed, but each folder only searched once.
+    pub fn scan(root: &Path) -> ModScan {
+        let mut scan = ModScan::default();
+        if root.is_dir() {
+            scan.scan_dir(root, &mut HashSet::new());
+        }
+        scan.mods.sort_by_key(|m| m.manifest.name.to_lowercase());
+        scan
+    }
+
+    fn scan_dir(&mut self, dir: &Path, visited: &mut HashSet<PathBuf>) {
+        // a link back up to a parent folder would be searched forever
+        if let Ok(real) = fs::canonicalize(dir) {
+            if !visited.insert(real) {
+                return;
+            }
+        }
+        let entries = match fs::read_dir(dir) {
+            Ok(e) => e,
+            Err(e) => {
+                self.errors.push(ModError {
+                    path: dir.to_path_buf(),
+                    error: e.to_string(),
+                });
+                return;
+            }
+        };
+
+        let mut folders: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        folders.sort();
+
+        for folder in folders {
+            let manifest_path = folder.join("manifest.json");
+            if !manifest_path.is_file() {
+                if !is_hidden(&folder) {
+                    self.scan_dir(&folder, visited);
+                }
+                continue;
+            }
+            match ModManifest::load(&manifest_path) {
+                Ok(manifest) => self.mods.push(InstalledMod {
+                    enabled: !is_hidden(&folder),
+                    path: folder,
+                    manifest,
+                }),
+                Err(error) => self.errors.push(ModError {
+                    path: folder,
+                    error,
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_and_trailing_commas_are_allowed() {
+        let manifest = ModManifest::parse(
+            r#"{
+                // written by hand
+                "Name": "Lookup Anything",
+                "Author": "Pathoschild",
+                "Version": "1.40.0",
+                "UniqueID": "Pathoschild.LookupAnything", /* the mod id */
+                "EntryDll": "LookupAnything.dll",
+                "UpdateKeys": ["Nexus:541",],
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "Lookup Anything");
+        assert_eq!(manifest.unique_id, "Pathoschild.LookupAnything");
+        assert_eq!(manifest.update_keys, ["Nexus:541"]);
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        let manifest = ModManifest::parse(
+            "\u{feff}{\"Name\": \"A\", \"Version\": \"1.0.0\", \"UniqueID\": \"a.a\"}",
+        )
+        .unwrap();
+        assert_eq!(manifest.unique_id, "a.a");
+    }
+
+    #[test]
+    fn field_names_match_in_any_case() {
+        let manifest = ModManifest::parse(
+            r#"{
+                "name": "Content Pack",
+                "version": "2.0.0",
+                "UniqueId": "someone.pack",
+                "contentPackFor": { "uniqueID": "Pathoschild.ContentPatcher", "minimumversion": "2.0.0" },
+                "DEPENDENCIES": [{ "UniqueId": "someone.core", "isRequired": false }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "Content Pack");
+        assert_eq!(manifest.unique_id, "someone.pack");
+        let pack_for = manifest.content_pack_for.unwrap();
+        assert_eq!(pack_for.unique_id, "Pathoschild.ContentPatcher");
+        assert_eq!(pack_for.minimum_version.as_deref(), Some("2.0.0"));
+        assert_eq!(manifest.dependencies[0].unique_id, "someone.core");
+        assert!(!manifest.dependencies[0].is_required);
+    }
+
+    #[test]
+    fn dependencies_are_required_by_default() {
+        let manifest = ModManifest::parse(
+            r#"{"Name": "A", "Version": "1.0.0", "UniqueID": "a.a",
+                "Dependencies": [{"UniqueID": "b.b", "MinimumVersion": "1.2.0"}]}"#,
+        )
+        .unwrap();
+        assert!(manifest.dependencies[0].is_required);
+        assert_eq!(
+            manifest.dependencies[0].minimum_version.as_deref(),
+            Some("1.2.0")
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_an_error() {
+        assert!(ModManifest::parse(r#"{"Name": "A", "Version": "1.0.0"}"#).is_err());
+        assert!(ModManifest::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Serialize;
+
+use crate::checks::Report;
+use crate::error::Error;
+use crate::mods::ModScan;
+
+/// Bumped whenever a field of the JSON output is renamed, removed or changes
+/// meaning. New fields may be added without a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct Output<T: Serialize> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+impl<T: Serialize> Output<T> {
+    pub fn print(data: T) -> Result<(), Error> {
+        let output = Output {
+            schema_version: SCHEMA_VERSION,
+            data,
+        };
+        match serde_json::to_string_pretty(&output) {
+            Ok(json) => {
+                println!("{json}");
+                Ok(())
+            }
+            Err(e) => Err(Error::Parse(e.to_string())),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct InstanceInfo {
+    pub name: String,
+    pub folder_name: String,
+    pub smapi_path: Option<String>,
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct InstanceList {
+    pub instances: Vec<InstanceInfo>,
+}
+
+#[derive(Serialize)]
+pub struct InstancePath {
+    pub instance: String,
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct ModInventory {
+    pub instance: String,
+    pub path: String,
+    #[serde(flatten)]
+    pub scan: ModScan,
+}
+
+#[derive(Serialize)]
+pub struct CheckResult {
+    pub instance: String,
+    pub blocking: bool,
+    #[serde(flatten)]
+    pub report: Report,
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    os::unix,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::detect::game_data_dir;
+use crate::error::Error;
+use crate::lock::is_alive;
+
+/// Remembers an active swap, so saves can be put back after a crash.
+const SWAP_STATE: &str = "saves-swap.json";
+
+/// The folder the game reads saves from.
+pub fn saves_dir() -> PathBuf {
+    game_data_dir().join("Saves")
+}
+
+/// Where the shared saves wait while an instance's own saves are swapped in.
+fn parked_saves() -> PathBuf {
+    game_data_dir().join("Saves.shared")
+}
+
+/// `state_dir` is the folder of the config, which holds the manager's state.
+fn state_path(state_dir: &Path) -> PathBuf {
+    state_dir.join(SWAP_STATE)
+}
+
+/// An instance's saves folder linked in place of the game's `Saves` while it
+/// runs. Only one swap can be active at a time.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavesSwap {
+    pub instance: String,
+    pub saves: PathBuf,
+    /// Process the swap was made for, `None` until it started.
+    pub pid: Option<u32>,
+    /// File the swap is remembered in.
+    #[serde(skip)]
+    state: PathBuf,
+}
+
+impl SavesSwap {
+    fn read(state_dir: &Path) -> Option<SavesSwap> {
+        let state = state_path(state_dir);
+        let contents = fs::read_to_string(&state).ok()?;
+        let swap: SavesSwap = serde_json::from_str(contents.as_str()).ok()?;
+        Some(SavesSwap { state, ..swap })
+    }
+
+    fn write(&self) -> Result<(), Error> {
+        let path = &self.state;
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                return Err(Error::Filesystem(format!("{}: {e}", parent.display())));
+            }
+        }
+        let contents = match serde_json::to_vec_pretty(self) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::Parse(e.to_string())),
+        };
+        match fs::write(path, contents) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Filesystem(format!("{}: {e}", path.display()))),
+        }
+    }
+
+    /// The swap of a game that is still running.
+    pub fn active(state_dir: &Path) -> Option<SavesSwap> {
+        let swap = SavesSwap::read(state_dir)?;
+        match swap.pid {
+            Some(pid) if is_alive(pid) => Some(swap),
+            _ => None,
+        }
+    }
+
+    /// Moves the shared saves aside and links `saves` in their place. The
+    /// state is written first so a crash at any point can be undone.
+    pub fn swap_in(state_dir: &Path, instance: &str, saves: &Path) -> Result<SavesSwap, Error> {
+        ensure_shared(state_dir)?;
+        if let Err(e) = fs::create_dir_all(saves) {
+            return Err(Error::Filesystem(format!("{}: {e}", saves.display())));
+        }
+        let swap = SavesSwap {
+            instance: instance.to_string(),
+            saves: saves.to_path_buf(),
+            pid: None,
+            state: state_path(state_dir),
+        };
+        swap.write()?;
+
+        let game_saves = saves_dir();
+        if game_saves.exists() || game_saves.is_symlink() {
+            if let Err(e) = fs::rename(&game_saves, parked_saves()) {
+                let _ = swap.restore();
+                return Err(Error::Filesystem(format!("{}: {e}", game_saves.display())));
+            }
+        }
+        if let Err(e) = unix::fs::symlink(saves, &game_saves) {
+            let _ = swap.restore();
+            return Err(Error::Filesystem(format!("{}: {e}", game_saves.display())));
+        }
+        Ok(swap)
+    }
+
+    pub fn set_pid(&mut self, pid: u32) -> Result<(), Error> {
+        self.pid = Some(pid);
+        self.write()
+    }
+
+    /// Puts the shared saves back. Safe to repeat after an interrupted restore.
+    pub fn restore(&self) -> Result<(), Error> {
+        let game_saves = saves_dir();
+        if game_saves.is_symlink() {
+            if let Err(e) = fs::remove_file(&game_saves) {
+                return Err(Error::Filesystem(format!("{}: {e}", game_saves.display())));
+            }
+        }
+        let parked = parked_saves();
+        if parked.exists() && !game_saves.exists() {
+            if let Err(e) = fs::rename(&parked, &game_saves) {
+                return Err(Error::Filesystem(format!("{}: {e}", parked.display())));
+            }
+        }
+        match fs::remove_file(&self.state) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(Error::Filesystem(e.to_string())),
+        }
+    }
+}
+
+/// Puts the shared saves back if a swap was left behind by a game or manager
+/// that is gone. Returns the instance whose saves were swapped out.
+pub fn recover(state_dir: &Path) -> Result<Option<String>, Error> {
+    let swap = match SavesSwap::read(state_dir) {
+        Some(swap) => swap,
+        None => return Ok(None),
+    };
+    if let Some(pid) = swap.pid {
+        if is_alive(pid) {
+            return Ok(None);
+        }
+    }
+    swap.restore()?;
+    Ok(Some(swap.instance))
+}
+
+/// Makes sure the game's `Saves` folder holds the shared saves, failing while
+/// an instance with its own saves runs.
+pub fn ensure_shared(state_dir: &Path) -> Result<(), Error> {
+    if let Some(active) = SavesSwap::active(state_dir) {
+        return Err(Error::Instance(format!(
+            "{} is running with its own saves, launch again after it exits",
+            active.instance
+        )));
+    }
+    recover(state_dir)?;
+    Ok(())
+}
+
+/// Restores the swap made for `pid`, if there is one.
+pub fn release(state_dir: &Path, pid: u32) -> Result<(), Error> {
+    match SavesSwap::read(state_dir) {
+        Some(swap) if swap.pid == Some(pid) => swap.restore(),
+        _ => Ok(()),
+    }
+}
+
+/// Which instance last played each save, keyed by the save folder's path.
+const PLAYED_SAVES: &str = "played-saves.json";
+
+const SEASONS: [&str; 4] = ["Spring", "Summer", "Fall", "Winter"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayedBy {
+    pub instance: String,
+    /// RFC 3339 timestamp of the launch.
+    pub started: String,
+}
+
+/// A save folder as listed in the save browser. Fields missing from its
+/// `SaveGameInfo` are left empty.
+#[derive(Debug, Clone)]
+pub struct SaveGame {
+    /// Name of the folder, like `Farm_123456789`.
+    pub folder: String,
+    /// Instance whose own saves folder holds the save, `None` for shared saves.
+    pub owner: Option<String>,
+    pub farm_name: String,
+    pub player_name: String,
+    pub day: u32,
+    /// 0 to 3, spring to winter.
+    pub season: u32,
+    pub year: u32,
+    pub played: Duration,
+    pub game_version: String,
+    pub played_by: Option<PlayedBy>,
+}
+
+impl SaveGame {
+    /// Reads `SaveGameInfo` in the save folder `path`. It is the farmer alone,
+    /// so the first `<name>` is the player's.
+    pub fn read(path: &Path, owner: Option<String>) -> Result<SaveGame, Error> {
+        let info = path.join("SaveGameInfo");
+        let xml = match fs::read_to_string(&info) {
+            Ok(xml) => xml,
+            Err(e) => return Err(Error::Filesystem(format!("{}: {e}", info.display()))),
+        };
+        let number = |tag: &str| {
+            tag_text(&xml, tag)
+                .and_then(|text| text.parse::<u64>().ok())
+                .unwrap_or_default()
+        };
+        Ok(SaveGame {
+            folder: file_name(path),
+            owner,
+            farm_name: tag_text(&xml, "farmName").unwrap_or_default(),
+            player_name: tag_text(&xml, "name").unwrap_or_default(),
+            day: number("dayOfMonthForSaveGame") as u32,
+            season: number("seasonForSaveGame") as u32,
+            year: number("yearForSaveGame") as u32,
+            played: Duration::from_millis(number("millisecondsPlayed")),
+            game_version: tag_text(&xml, "gameVersion").unwrap_or_default(),
+            played_by: None,
+        })
+    }
+
+    /// In-game date, like `Spring 5, Year 2`.
+    pub fn date(&self) -> String {
+        let season = SEASONS.get(self.season as usize).unwrap_or(&"?");
+        format!("{season} {}, Year {}", self.day, self.year)
+    }
+}
+
+/// Text of the first `<tag>` element, with the XML escapes undone.
+fn tag_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{tag}>");
+    let start = xml.find(open.as_str())? + open.len();
+    let end = start + xml[start..].find(format!("</{tag}>").as_str())?;
+    Some(
+        xml[start..end]
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+fn file_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+/// Where the shared saves are right now, which is aside while an instance
+/// plays on its own.
+pub fn shared_saves(state_dir: &Path) -> PathBuf {
+    let parked = parked_saves();
+    if SavesSwap::read(state_dir).is_some() && parked.exists() {
+        parked
+    } else {
+        saves_dir()
+    }
+}
+
+/// Save folders in `dir`. `root` is where they normally are, which the
+/// record of the instance that last played them refers to.
+pub fn list_saves(
+    state_dir: &Path,
+    dir: &Path,
+    root: &Path,
+    owner: Option<String>,
+) -> Vec<SaveGame> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let played = played_saves(state_dir);
+    let mut saves: Vec<SaveGame> = entries
+        .flatten()
+        .filter(|entry| entry.path().join("SaveGameInfo").is_file())
+        .filter_map(|entry| SaveGame::read(&entry.path(), owner.clone()).ok())
+        .map(|mut save| {
+            let key = root.join(&save.folder);
+            save.played_by = played.get(&key.to_string_lossy().to_string()).cloned();
+            save
+        })
+        .collect();
+    saves.sort_by(|a, b| a.folder.cmp(&b.folder));
+    saves
+}
+
+fn played_saves_path(state_dir: &Path) -> PathBuf {
+    state_dir.join(PLAYED_SAVES)
+}
+
+fn played_saves(state_dir: &Path) -> BTreeMap<String, PlayedBy> {
+    match fs::read_to_string(played_saves_path(state_dir)) {
+        Ok(contents) => serde_json::from_str(contents.as_str()).unwrap_or_default(),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// Newest modification of a file directly in `dir`.
+fn last_modified(dir: &Path) -> Option<SystemTime> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .filter_map(|metadata| metadata.modified().ok())
+        .max()
+}
+
+/// Marks the saves in `root` that changed since `started` as last played by
+/// `instance`. The game only writes a save when a day ends, so saves that
+/// were just loaded aren't marked.
+pub fn record_played(
+    state_dir: &Path,
+    instance: &str,
+    root: &Path,
+    started: SystemTime,
+) -> Result<(), Error> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+    let mut played = played_saves(state_dir);
+    let mut changed = false;
+    for entry in entries.flatten() {
+        match last_modified(&entry.path()) {
+            Some(modified) if modified >= started => (),
+            _ => continue,
+        }
+        let started: DateTime<Local> = started.into();
+        played.insert(
+            root.join(entry.file_name()).to_string_lossy().to_string(),
+            PlayedBy {
+                instance: instance.to_string(),
+                started: started.to_rfc3339(),
+            },
+        );
+        changed = true;
+    }
+    if !changed {
+        return Ok(());
+    }
+    write_played_saves(state_dir, &played)
+}
+
+/// Moves the records of saves last played on `old_name` over to `new_name`.
+pub fn rename_player(state_dir: &Path, old_name: &str, new_name: &str) -> Result<(), Error> {
+    let mut played = played_saves(state_dir);
+    let mut changed = false;
+    for played_by in played.values_mut() {
+        if played_by.instance == old_name {
+            played_by.instance = new_name.to_string();
+            changed = true;
+        }
+    }
+    if !changed {
+        return Ok(());
+    }
+    write_played_saves(state_dir, &played)
+}
+
+fn write_played_saves(state_dir: &Path, played: &BTreeMap<String, PlayedBy>) -> Result<(), Error> {
+    let path = played_saves_path(state_dir);
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(Error::Filesystem(format!("{}: {e}", parent.display())));
+        }
+    }
+    let contents = match serde_json::to_vec_pretty(played) {
+        Ok(c) => c,
+        Err(e) => return Err(Error::Parse(e.to_string())),
+    };
+    match fs::write(&path, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::Filesystem(format!("{}: {e}", path.display()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    const SAVE_GAME_INFO: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<Farmer xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:xsd="http://www.w3.org/2001/XMLSchema">
+  <name>Abi &amp; Sam</name>
+  <farmName>&lt;Sunny&gt; Acres</farmName>
+  <items><Item><name>Axe</name></Item></items>
+  <dayOfMonthForSaveGame>5</dayOfMonthForSaveGame>
+  <seasonForSaveGame>2</seasonForSaveGame>
+  <yearForSaveGame>3</yearForSaveGame>
+  <millisecondsPlayed>7260000</millisecondsPlayed>
+  <gameVersion>1.6.8</gameVersion>
+</Farmer>"#;
+
+    #[test]
+    fn tag_text_undoes_escapes() {
+        assert_eq!(
+            tag_text(SAVE_GAME_INFO, "name").as_deref(),
+            Some("Abi & Sam")
+        );
+        assert_eq!(
+            tag_text("<a>&quot;&apos;&amp;lt;</a>", "a").as_deref(),
+            Some("\"'&lt;")
+        );
+        assert_eq!(tag_text("<farmName />", "farmName"), None);
+        assert_eq!(tag_text("<a>unclosed", "a"), None);
+    }
+
+    #[test]
+    fn reads_save_game_info() {
+        let dir = env::temp_dir().join(format!(
+            "smapi-instance-manager-test-{}-save",
+            std::process::id()
+        ));
+        let save = dir.join("Sam_123456789");
+        fs::create_dir_all(&save).unwrap();
+        fs::write(save.join("SaveGameInfo"), SAVE_GAME_INFO).unwrap();
+
+        let game = SaveGame::read(&save, Some("Default".to_string())).unwrap();
+        assert_eq!(game.folder, "Sam_123456789");
+        assert_eq!(game.owner.as_deref(), Some("Default"));
+        assert_eq!(game.player_name, "Abi & Sam");
+        assert_eq!(game.farm_name, "<Sunny> Acres");
+        assert_eq!(game.date(), "Fall 5, Year 3");
+        assert_eq!(game.played, Duration::from_secs(7260));
+        assert_eq!(game.game_version, "1.6.8");
+
+        fs::write(save.join("SaveGameInfo"), "<Farmer></Farmer>").unwrap();
+        let game = SaveGame::read(&save, None).unwrap();
+        assert_eq!(game.farm_name, "");
+        assert_eq!(game.date(), "Spring 0, Year 0");
+
+        assert!(SaveGame::read(&dir.join("missing"), None).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
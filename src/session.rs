@@ -0,0 +1,227 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::error::Error;
+use crate::logs::ConsoleLine;
+
+pub enum SessionStatus {
+    Running,
+    Exited(ExitStatus),
+    /// Waiting for the process failed, so it is no longer tracked.
+    Lost(String),
+}
+
+/// A launched instance. The process gets its own process group so stopping it
+/// also stops the game started by wrappers like `steam-run`.
+pub struct Session {
+    pub instance: String,
+    /// Mods folder the instance was started with.
+    pub mods: PathBuf,
+    pub child: Child,
+    pub started: SystemTime,
+    /// When the process was noticed to have stopped.
+    pub ended: Option<SystemTime>,
+    pub status: SessionStatus,
+    /// Whether the output is captured instead of shown in a terminal.
+    pub headless: bool,
+    /// Output of headless launches, read on background threads.
+    output: Option<Receiver<String>>,
+    pub console: Vec<ConsoleLine>,
+}
+
+impl Session {
+    /// Starts `command` without waiting for it. Headless sessions collect
+    /// stdout and stderr into `console` instead of inheriting them.
+    pub fn spawn(
+        instance: String,
+        mods: PathBuf,
+        mut command: Command,
+        headless: bool,
+    ) -> Result<Session, Error> {
+        command.process_group(0);
+        if headless {
+            command
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+        }
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                return Err(Error::Launch(format!(
+                    "{}: {e}",
+                    command.get_program().to_string_lossy()
+                )))
+            }
+        };
+
+        let mut output = None;
+        if headless {
+            let (sender, receiver) = mpsc::channel();
+            if let Some(stdout) = child.stdout.take() {
+                forward_lines(stdout, sender.clone());
+            }
+            if let Some(stderr) = child.stderr.take() {
+                forward_lines(stderr, sender);
+            }
+            output = Some(receiver);
+        }
+        Ok(Session {
+            instance,
+            mods,
+            child,
+            started: SystemTime::now(),
+            ended: None,
+            status: SessionStatus::Running,
+            headless,
+            output,
+            console: Vec::new(),
+        })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.status, SessionStatus::Running)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed().unwrap_or_default()
+    }
+
+    /// Collects new output and checks whether the process is still running.
+    /// Returns true if it stopped since the last poll.
+    pub fn poll(&mut self) -> bool {
+        self.read_output(None);
+        if !self.is_running() {
+            return false;
+        }
+        match self.child.try_wait() {
+            Ok(Some(status)) => {
+                // the reader threads may still hold the last lines
+                self.read_output(Some(Duration::from_millis(100)));
+                self.status = SessionStatus::Exited(status);
+            }
+            Ok(None) => return false,
+            Err(e) => self.status = SessionStatus::Lost(e.to_string()),
+        }
+        self.ended = Some(SystemTime::now());
+        true
+    }
+
+    /// Whether the session ran at some point after `time`.
+    pub fn ran_after(&self, time: SystemTime) -> bool {
+        match self.ended {
+            Some(ended) => ended > time,
+            None => true,
+        }
+    }
+
+    fn read_output(&mut self, wait: Option<Duration>) {
+        let output = match &self.output {
+            Some(output) => output,
+            None => return,
+        };
+        loop {
+            let text = match wait {
+                Some(timeout) => match output.recv_timeout(timeout) {
+                    Ok(text) => text,
+                    Err(_) => break,
+                },
+                None => match output.try_recv() {
+                    Ok(text) => text,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.output = None;
+                        break;
+                    }
+                },
+            };
+            let line = ConsoleLine::new(text, self.console.last());
+            self.console.push(line);
+        }
+    }
+
+    /// Asks the whole process group to quit, falling back to killing the
+    /// launched process directly.
+    pub fn stop(&mut self) -> Result<(), Error> {
+        if !self.is_running() {
+            return Ok(());
+        }
+        if stop_process_group(self.pid()).is_ok() {
+            return Ok(());
+        }
+        match self.child.kill() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Launch(format!(
+                "failed to stop {}: {e}",
+                self.instance
+            ))),
+        }
+    }
+
+    /// Kills the whole process group and waits for the launched process, for
+    /// sessions that didn't stop when asked.
+    pub fn kill(&mut self) -> Result<(), Error> {
+        if signal_process_group(self.pid(), "-KILL").is_err() {
+            let _ = self.child.kill();
+        }
+        match self.child.wait() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Launch(format!(
+                "failed to stop {}: {e}",
+                self.instance
+            ))),
+        }
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// Sends SIGTERM to the process group led by `pid`, which every session has.
+pub fn stop_process_group(pid: u32) -> Result<(), Error> {
+    signal_process_group(pid, "-TERM")
+}
+
+fn signal_process_group(pid: u32, signal: &str) -> Result<(), Error> {
+    let group = format!("-{pid}");
+    match Command::new("kill")
+        .args([signal, "--", group.as_str()])
+        .status()
+    {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(Error::Launch(format!("kill {group}: {status}"))),
+        Err(e) => Err(Error::Launch(format!("kill: {e}"))),
+    }
+}
+
+fn forward_lines<R: Read + Send + 'static>(stream: R, sender: Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,173 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::logs::{self, LogLevel, SmapiLog, LOG_DIR};
+
+/// Summaries kept per instance in `.logs/sessions.json`.
+const HISTORY_LENGTH: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModProblems {
+    pub source: String,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// What happened during one launch of an instance.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionSummary {
+    pub instance: String,
+    /// RFC 3339 timestamp of the launch.
+    pub started: String,
+    pub duration_secs: u64,
+    /// `None` if SMAPI was killed by a signal or lost track of.
+    pub exit_code: Option<i32>,
+    /// Archived log of the session, if SMAPI wrote one.
+    pub log: Option<PathBuf>,
+    /// Mods that logged errors or warnings, most errors first.
+    pub problems: Vec<ModProblems>,
+    /// Entries of SMAPI's "Skipped mods" section.
+    pub skipped_mods: Vec<String>,
+}
+
+impl SessionSummary {
+    pub fn new(
+        instance: String,
+        started: SystemTime,
+        exit_code: Option<i32>,
+        log: Option<&SmapiLog>,
+    ) -> SessionSummary {
+        let (problems, skipped_mods) = match log {
+            Some(log) => (count_problems(log), skipped_mods(log)),
+            None => (Vec::new(), Vec::new()),
+        };
+        let duration_secs = started.elapsed().unwrap_or_default().as_secs();
+        let started: DateTime<Local> = started.into();
+        SessionSummary {
+            instance,
+            started: started.to_rfc3339(),
+            duration_secs,
+            exit_code,
+            log: log.map(|log| log.path.clone()),
+            problems,
+            skipped_mods,
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.problems.iter().map(|problems| problems.errors).sum()
+    }
+}
+
+fn count_problems(log: &SmapiLog) -> Vec<ModProblems> {
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for entry in log.filtered(LogLevel::Warn, None) {
+        let count = counts.entry(entry.source.as_str()).or_default();
+        if entry.level == LogLevel::Warn {
+            count.1 += 1;
+        } else {
+            count.0 += 1;
+        }
+    }
+    let mut problems: Vec<ModProblems> = counts
+        .into_iter()
+        .map(|(source, (errors, warnings))| ModProblems {
+            source: source.to_string(),
+            errors,
+            warnings,
+        })
+        .collect();
+    problems.sort_by_key(|problems| Reverse((problems.errors, problems.warnings)));
+    problems
+}
+
+/// SMAPI lists mods it couldn't load right after a `Skipped mods` heading,
+/// one `- Name 1.0 because ...` message each, all at the heading's level.
+fn skipped_mods(log: &SmapiLog) -> Vec<String> {
+    let start = match log
+        .entries
+        .iter()
+        .position(|entry| entry.source == "SMAPI" && entry.message.trim() == "Skipped mods")
+    {
+        Some(index) => index,
+        None => return Vec::new(),
+    };
+    let level = log.entries[start].level;
+    let mut skipped = Vec::new();
+    for entry in &log.entries[start + 1..] {
+        if entry.source != "SMAPI" || entry.level != level {
+            break;
+        }
+        if let Some(skipped_mod) = entry.message.trim().strip_prefix("- ") {
+            skipped.push(skipped_mod.to_string());
+        }
+    }
+    skipped
+}
+
+fn history_path(mods: &Path) -> PathBuf {
+    mods.join(LOG_DIR).join("sessions.json")
+}
+
+/// Summaries of earlier sessions of the instance using `mods`, oldest first.
+pub fn history(mods: &Path) -> Vec<SessionSummary> {
+    match fs::read_to_string(history_path(mods)) {
+        Ok(contents) => serde_json::from_str(contents.as_str()).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn append_history(mods: &Path, summary: &SessionSummary) -> Result<(), Error> {
+    let mut sessions = history(mods);
+    sessions.push(summary.clone());
+    if sessions.len() > HISTORY_LENGTH {
+        sessions.drain(..sessions.len() - HISTORY_LENGTH);
+    }
+    let path = history_path(mods);
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(Error::Filesystem(format!("{}: {e}", parent.display())));
+        }
+    }
+    let contents = match serde_json::to_vec_pretty(&sessions) {
+        Ok(c) => c,
+        Err(e) => return Err(Error::Parse(e.to_string())),
+    };
+    match fs::write(&path, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Error::Filesystem(format!("{}: {e}", path.display()))),
+    }
+}
+
+/// Archives the session's log, summarises it and adds the summary to the
+/// instance's history. `log_replaced` means another SMAPI started since, so
+/// `SMAPI-latest.txt` is its log and is left alone.
+pub fn finish_session(
+    instance: &str,
+    mods: &Path,
+    started: SystemTime,
+    exit_code: Option<i32>,
+    log_replaced: bool,
+) -> Result<SessionSummary, Error> {
+    let archived = if log_replaced {
+        None
+    } else {
+        logs::archive_latest(mods, started)?
+    };
+    let log = match archived {
+        Some(path) => Some(SmapiLog::load(&path)?),
+        None => None,
+    };
+    let summary = SessionSummary::new(instance.to_string(), started, exit_code, log.as_ref());
+    append_history(mods, &summary)?;
+    Ok(summary)
+}
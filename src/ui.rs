@@ -1,15 +1,20 @@
 use ratatui::{
-    layout::{Constraint, Layout, Rect},
+    layout::{Constraint, Layout, Rect, Size},
     prelude::Direction,
-    style::{Color, Modifier, Style, Stylize},
+    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Cell, Clear, Gauge, List, ListItem, Paragraph, Row, Table, Wrap},
     Frame,
 };
+use std::time::Duration;
 
-use crate::app::{App, CurrentScreen, CurrentlyAdding};
+use crate::app::{App, CurrentScreen, CurrentlyAdding, Focus};
+use crate::checks::DependencyProblem;
+use crate::launch::{LaunchMode, TERMINAL_PRESETS, WRAPPER_PRESETS};
+use crate::logs::LogLevel;
+use crate::session::{format_duration, Session, SessionStatus};
 
-pub fn ui(frame: &mut Frame, app: &App) {
+pub fn ui(frame: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -23,11 +28,21 @@ pub fn ui(frame: &mut Frame, app: &App) {
         .borders(Borders::ALL)
         .style(Style::default());
 
-    let title = Paragraph::new(Text::styled(
+    let mut title_spans = vec![Span::styled(
         "SMAPI Instance Manager",
         Style::default().fg(Color::Green),
-    ))
-    .block(title_block);
+    )];
+    title_spans.push(Span::styled(
+        format!(" | {}: {} mods", app.current_instance, app.mods.mods.len()),
+        Style::default().fg(Color::White),
+    ));
+    if !app.mods.errors.is_empty() {
+        title_spans.push(Span::styled(
+            format!(", {} failed to load", app.mods.errors.len()),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    let title = Paragraph::new(Line::from(title_spans)).block(title_block);
     frame.render_widget(title, chunks[0]);
 
     let mut list_instances = Vec::<ListItem>::new();
@@ -36,27 +51,143 @@ pub fn ui(frame: &mut Frame, app: &App) {
         .add_modifier(Modifier::BOLD);
 
     for key in app.manager.instances.keys().cloned() {
-        if key == app.current_instance {
-            list_instances.push(ListItem::new(Line::from(Span::styled(
-                format!("{: <25}", key),
-                active_style,
-            ))));
+        let mut spans = if key == app.current_instance {
+            vec![Span::styled(format!("{: <25}", key), active_style)]
         } else {
-            list_instances.push(ListItem::new(Line::from(Span::styled(
+            vec![Span::styled(
                 format!("{: <25}", key),
                 Style::default().fg(Color::Yellow),
-            ))));
+            )]
+        };
+        if let Some(session) = app.sessions.get(&key) {
+            if session.is_running() {
+                spans.push(Span::styled(
+                    format!("running {}", format_duration(session.elapsed())),
+                    Style::default().fg(Color::LightGreen),
+                ));
+            }
         }
+        list_instances.push(ListItem::new(Line::from(spans)));
+    }
+
+    let focused_style = Style::default().fg(Color::Green);
+    let mut instances_block = Block::default().title("Instances").borders(Borders::ALL);
+    let mut mods_block = Block::default().title("Mods").borders(Borders::ALL);
+    match app.focus {
+        Focus::Instances => instances_block = instances_block.border_style(focused_style),
+        Focus::Mods => mods_block = mods_block.border_style(focused_style),
+    }
+
+    let main_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(chunks[1]);
+
+    let list = List::new(list_instances).block(instances_block);
+    frame.render_widget(list, main_chunks[0]);
+
+    let mut mod_rows = Vec::<Row>::new();
+    for installed in &app.mods.mods {
+        let manifest = &installed.manifest;
+        let content_pack_for = match &manifest.content_pack_for {
+            Some(parent) => parent.unique_id.clone(),
+            None => String::new(),
+        };
+        let style = if installed.enabled {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        mod_rows.push(
+            Row::new(vec![
+                Cell::from(manifest.name.clone()),
+                Cell::from(manifest.version.clone()),
+                Cell::from(manifest.author.clone()),
+                Cell::from(content_pack_for),
+            ])
+            .style(style),
+        );
+    }
+    for failed in &app.mods.errors {
+        let folder = match failed.path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => failed.path.to_string_lossy().to_string(),
+        };
+        mod_rows.push(
+            Row::new(vec![
+                Cell::from(folder),
+                Cell::from(""),
+                Cell::from(""),
+                Cell::from(failed.error.clone()),
+            ])
+            .style(Style::default().fg(Color::Red)),
+        );
     }
 
-    let list = List::new(list_instances);
-    frame.render_widget(list, chunks[1]);
+    let mods_table = Table::new(
+        mod_rows,
+        [
+            Constraint::Percentage(35),
+            Constraint::Percentage(15),
+            Constraint::Percentage(20),
+            Constraint::Percentage(30),
+        ],
+    )
+    .header(
+        Row::new(vec!["Name", "Version", "Author", "Content Pack For"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(mods_block)
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(mods_table, main_chunks[1], &mut app.mod_state);
 
-    let current_navigation_text = vec![
+    let mut current_navigation_text = vec![
         match app.screen {
+            CurrentScreen::Setup => {
+                Span::styled("First Run Setup", Style::default().fg(Color::Yellow))
+            }
             CurrentScreen::Main => Span::styled("Main Menu", Style::default().fg(Color::Green)),
             CurrentScreen::Add => Span::styled("Adding Menu", Style::default().fg(Color::Yellow)),
             CurrentScreen::Remove => Span::styled("Removing Menu", Style::default().fg(Color::Red)),
+            CurrentScreen::Checks => {
+                Span::styled("Checking Instance", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::DeleteDuplicate => {
+                Span::styled("Deleting Duplicates", Style::default().fg(Color::Red))
+            }
+            CurrentScreen::Install | CurrentScreen::ConfirmInstall | CurrentScreen::InboxPrompt => {
+                Span::styled("Installing Mods", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::SetInbox => {
+                Span::styled("Setting Inbox", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::Clone | CurrentScreen::CloneMode => {
+                Span::styled("Cloning Menu", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::Edit | CurrentScreen::EditRenameFolder => {
+                Span::styled("Editing Menu", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::LaunchSettings => {
+                Span::styled("Launch Settings", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::Console => {
+                Span::styled("SMAPI Output", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::Stop => Span::styled("Stopping Menu", Style::default().fg(Color::Red)),
+            CurrentScreen::LockConflict => {
+                Span::styled("Already Running", Style::default().fg(Color::Red))
+            }
+            CurrentScreen::Logs => Span::styled("SMAPI Log", Style::default().fg(Color::Yellow)),
+            CurrentScreen::Summary => {
+                Span::styled("Session Summary", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::Saves => Span::styled("Saves", Style::default().fg(Color::Yellow)),
+            CurrentScreen::Backups => {
+                Span::styled("Save Backups", Style::default().fg(Color::Yellow))
+            }
+            CurrentScreen::RestoreBackup => {
+                Span::styled("Restoring Save", Style::default().fg(Color::Red))
+            }
             CurrentScreen::Exit => {
                 Span::styled("Exiting Menu", Style::default().fg(Color::LightRed))
             }
@@ -75,23 +206,41 @@ pub fn ui(frame: &mut Frame, app: &App) {
                     CurrentlyAdding::SmapiPath => {
                         Span::styled("Editing Smapi Path", Style::default().fg(Color::Green))
                     }
+                    CurrentlyAdding::SavesDir => {
+                        Span::styled("Editing Saves Folder", Style::default().fg(Color::Green))
+                    }
                 }
             } else {
                 Span::styled("Not Editing Anything", Style::default().fg(Color::DarkGray))
             }
         },
     ];
+    if let Some(status) = &app.status {
+        current_navigation_text.push(Span::styled(" | ", Style::default().fg(Color::White)));
+        current_navigation_text.push(Span::styled(
+            status.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
 
     let mode_footer = Paragraph::new(Line::from(current_navigation_text))
         .block(Block::default().borders(Borders::ALL));
     let current_key_hints = {
         match app.screen {
-            CurrentScreen::Add => Span::styled(
+            CurrentScreen::Setup => Span::styled(
+                "(up) and (down) to select a detected install/type a path to use another one/(Enter) to confirm/(Esc) to quit",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Add | CurrentScreen::Edit => Span::styled(
                 "(Esc) to cancle/(Tab) to switch boxes/(Enter) to complete", 
                 Style::default().fg(Color::Red)
             ),
+            CurrentScreen::EditRenameFolder => Span::styled(
+                "(y) to rename the folder on disk/(n) to only change the config/(Esc) to go back",
+                Style::default().fg(Color::Red)
+            ),
             CurrentScreen::Main => Span::styled(
-                "(a) to add new instance/(q) to quit/(r) to remove selected instance/(o) to open folder/(Enter) to start selected instance/ (up) and (down) to selecte instance", 
+                "(a) to add new instance/(q) to quit/(r) to remove selected instance/(o) to open folder/(Enter) to start selected instance/ (up) and (down) to selecte instance/(Tab) to switch between instances and mods/(Space) to enable or disable selected mod/(v) to check dependencies/(e) to edit selected instance/(c) to clone selected instance/(i) to install a mod archive/(w) to set the downloads folder to watch/(l) to change how the selected instance is launched/(h) to show the output of headless launches/(k) to stop the selected instance/(g) to read the SMAPI log of the last session/(p) to see how past sessions went/(s) to browse saves", 
                 Style::default().fg(Color::Red)
             ),
             CurrentScreen::Exit => Span::styled(
@@ -102,6 +251,78 @@ pub fn ui(frame: &mut Frame, app: &App) {
                 "(n) to cancle/(y) to remove instance/(a) to delete with folder", 
                 Style::default().fg(Color::Red)
             ),
+            CurrentScreen::Checks if app.launch_pending => Span::styled(
+                "(y) to launch anyway/(n) or (Esc) to cancel/(up) and (down) to scroll/(Tab) to select duplicate/(d) to disable or (x) to delete older copies",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Checks => Span::styled(
+                "(Esc) to go back to main menu/(up) and (down) to scroll/(Tab) to select duplicate/(d) to disable or (x) to delete older copies",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::DeleteDuplicate => Span::styled(
+                "(y) to delete the older copies/(n) to cancle",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Install => Span::styled(
+                "(Esc) to cancle/(Enter) to install the archive",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::ConfirmInstall => Span::styled(
+                "(y) to install and upgrade/(n) to cancle",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::InboxPrompt => Span::styled(
+                "(y) to install/(n) to ignore the archive",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Clone => Span::styled(
+                "(Esc) to cancle/(Tab) to switch boxes/(Enter) to continue",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::CloneMode => Span::styled(
+                "(f) to copy the full folder/(c) to copy only manifests and configs/(Esc) to go back",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::SetInbox => Span::styled(
+                "(Esc) to cancle/(Enter) to save, leave empty to stop watching",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Stop => Span::styled(
+                "(y) to stop the instance/(n) to cancle",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Logs => Span::styled(
+                "(up) and (down) or (PageUp) and (PageDown) to scroll/(left) and (right) for newer and older logs/(l) to change the lowest level shown/(m) to show one mod only/(Esc) to go back",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Summary => Span::styled(
+                "(left) and (right) for newer and older sessions/(g) to open the session's log/(Esc) to go back",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Saves => Span::styled(
+                "(up) and (down) to select a save/(Enter) to select the instance that last played it/(b) to see its backups/(Esc) to go back",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Backups => Span::styled(
+                "(up) and (down) to select a backup/(Enter) to restore it/(Esc) to go back",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::RestoreBackup => Span::styled(
+                "(y) to restore the backup/(n) to cancle",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::LockConflict => Span::styled(
+                "(k) to stop the running game/(y) to launch anyway/(n) to cancle",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::Console => Span::styled(
+                "(up) and (down) or (PageUp) and (PageDown) to scroll/(Home) to jump to the start/(End) to follow new output/(Esc) to go back",
+                Style::default().fg(Color::Red)
+            ),
+            CurrentScreen::LaunchSettings => Span::styled(
+                "(up) and (down) to select terminal, wrapper or mode/(left) and (right) to change it/(g) to switch between global and instance/(x) to use the global settings for this instance/(Enter) to save/(Esc) to cancle",
+                Style::default().fg(Color::Red)
+            ),
         }
     };
 
@@ -116,27 +337,40 @@ pub fn ui(frame: &mut Frame, app: &App) {
     frame.render_widget(key_notest_footer, footer_chunks[1]);
 
     if let Some(adding) = &app.adding {
+        let popup_title = match app.screen {
+            CurrentScreen::Clone | CurrentScreen::CloneMode => {
+                format!("Clone {}", app.current_instance)
+            }
+            CurrentScreen::Edit | CurrentScreen::EditRenameFolder => {
+                format!("Edit {}", app.current_instance)
+            }
+            _ => "Add a new instance".to_string(),
+        };
         let popup_block = Block::default()
-            .title("Add a new instance")
+            .title(popup_title)
             .borders(Borders::NONE)
             .style(Style::default().bg(Color::DarkGray));
 
-        let area = centered_rect(60, 40, frame.area());
+        let area = centered_rect(60, 50, frame.area());
         frame.render_widget(popup_block, area);
 
         let popup_chunks = Layout::default()
             .direction(Direction::Vertical)
             .margin(1)
             .constraints([
-                Constraint::Percentage(100 / 3),
-                Constraint::Percentage(100 / 3),
-                Constraint::Percentage(100 / 3),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
             ])
             .split(area);
 
         let mut name_block = Block::default().title("Name").borders(Borders::ALL);
         let mut folder_name_block = Block::default().title("Folder Name").borders(Borders::ALL);
         let mut smapi_path_block = Block::default().title("SMAPI Path").borders(Borders::ALL);
+        let mut saves_dir_block = Block::default()
+            .title("Saves Folder (empty to share the game's saves)")
+            .borders(Borders::ALL);
 
         let active_style = Style::default().bg(Color::LightYellow).fg(Color::Black);
         let name_used_style = Style::default().bg(Color::LightRed).fg(Color::Black);
@@ -147,9 +381,10 @@ pub fn ui(frame: &mut Frame, app: &App) {
                 folder_name_block = folder_name_block.style(active_style)
             }
             CurrentlyAdding::SmapiPath => smapi_path_block = smapi_path_block.style(active_style),
+            CurrentlyAdding::SavesDir => saves_dir_block = saves_dir_block.style(active_style),
         };
 
-        if app.manager.instances.contains_key(&app.name_input) {
+        if app.name_is_taken() {
             name_block = name_block.style(name_used_style);
         }
 
@@ -160,8 +395,222 @@ pub fn ui(frame: &mut Frame, app: &App) {
             Paragraph::new(app.folder_name_input.clone()).block(folder_name_block);
         frame.render_widget(folder_name_text, popup_chunks[1]);
 
-        let smapi_path_text = Paragraph::new(app.smapi_path_input.clone()).block(smapi_path_block);
+        let smapi_path_input = match app.screen {
+            CurrentScreen::Clone | CurrentScreen::CloneMode => {
+                format!("(same as {})", app.current_instance)
+            }
+            _ => app.smapi_path_input.clone(),
+        };
+        let smapi_path_text = Paragraph::new(smapi_path_input).block(smapi_path_block);
         frame.render_widget(smapi_path_text, popup_chunks[2]);
+
+        let saves_dir_input = match app.screen {
+            CurrentScreen::Clone | CurrentScreen::CloneMode => "(shared)".to_string(),
+            _ => app.saves_dir_input.clone(),
+        };
+        let saves_dir_text = Paragraph::new(saves_dir_input).block(saves_dir_block);
+        frame.render_widget(saves_dir_text, popup_chunks[3]);
+    }
+
+    if let CurrentScreen::EditRenameFolder = app.screen {
+        let popup_block = Block::default()
+            .title("Y/N")
+            .borders(Borders::ALL)
+            .style(Style::default().bg(Color::DarkGray));
+        let text = Text::styled(
+            format!(
+                "The folder name changed to {}. Rename the folder on disk too?",
+                app.folder_name_input
+            ),
+            Style::default().fg(Color::Yellow),
+        );
+        let paragraph = Paragraph::new(text)
+            .block(popup_block)
+            .wrap(Wrap { trim: false });
+        let area = centered_rect(50, 20, frame.area());
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, area);
+    }
+
+    if let CurrentScreen::CloneMode = app.screen {
+        let popup_block = Block::default()
+            .title("Clone")
+            .borders(Borders::ALL)
+            .style(Style::default().bg(Color::DarkGray));
+        let text = Text::styled(
+            "(f) copy the whole folder\n(c) copy only manifest.json and config.json files",
+            Style::default().fg(Color::Yellow),
+        );
+        let paragraph = Paragraph::new(text).block(popup_block);
+        let area = centered_rect(50, 20, frame.area());
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, area);
+    }
+
+    if let CurrentScreen::Setup = app.screen {
+        render_setup(frame, app);
+    }
+
+    if let CurrentScreen::Checks | CurrentScreen::DeleteDuplicate = app.screen {
+        render_report(frame, app);
+    }
+
+    if let CurrentScreen::DeleteDuplicate = app.screen {
+        if let Some(duplicate) = app.report.duplicates.get(app.duplicate_index) {
+            let popup_block = Block::default()
+                .title("Y/N")
+                .borders(Borders::NONE)
+                .style(Style::default().bg(Color::DarkGray));
+            let newest = duplicate.newest();
+            let mut lines = vec![Line::from(Span::styled(
+                format!(
+                    "Delete {} older copies of {} for good, keeping {} at {}?",
+                    duplicate.older_copies().len(),
+                    duplicate.unique_id,
+                    newest.manifest.version,
+                    newest.path.display()
+                ),
+                Style::default().fg(Color::Red),
+            ))];
+            if duplicate.newest_is_tied() {
+                lines.push(Line::from(Span::styled(
+                    "Other copies have the same version, so the kept one is just the first by path.",
+                    Style::default().fg(Color::Yellow),
+                )));
+            }
+            let prompt = Paragraph::new(lines)
+                .block(popup_block)
+                .wrap(Wrap { trim: false });
+            let area = centered_rect(60, 25, frame.area());
+            frame.render_widget(Clear, area);
+            frame.render_widget(prompt, area);
+        }
+    }
+
+    if let CurrentScreen::LaunchSettings = app.screen {
+        render_launch_settings(frame, app);
+    }
+
+    if let CurrentScreen::Console = app.screen {
+        render_console(frame, app);
+    }
+
+    if let CurrentScreen::Logs = app.screen {
+        render_log(frame, app);
+    }
+
+    if let CurrentScreen::Summary = app.screen {
+        render_summary(frame, app);
+    }
+
+    if let CurrentScreen::Saves = app.screen {
+        render_saves(frame, app);
+    }
+
+    if let CurrentScreen::Backups | CurrentScreen::RestoreBackup = app.screen {
+        render_backups(frame, app);
+    }
+
+    if let CurrentScreen::RestoreBackup = app.screen {
+        if let Some(backup) = app.selected_backup() {
+            let popup_block = Block::default()
+                .title("Y/N")
+                .borders(Borders::NONE)
+                .style(Style::default().bg(Color::DarkGray));
+            let prompt = Paragraph::new(Text::styled(
+                format!(
+                    "Roll {} back to {}? The save is backed up first.",
+                    backup.save,
+                    backup.time.format("%Y-%m-%d %H:%M:%S")
+                ),
+                Style::default().fg(Color::Red),
+            ))
+            .block(popup_block)
+            .wrap(Wrap { trim: false });
+            let area = centered_rect(60, 25, frame.area());
+            frame.render_widget(Clear, area);
+            frame.render_widget(prompt, area);
+        }
+    }
+
+    if let CurrentScreen::Stop = app.screen {
+        if let Some(session) = app.current_session() {
+            let popup_block = Block::default()
+                .title("Y/N")
+                .borders(Borders::NONE)
+                .style(Style::default().bg(Color::DarkGray));
+            let prompt = Paragraph::new(Text::styled(
+                format!(
+                    "Stop {} ({})? Unsaved progress is lost.",
+                    session.instance,
+                    session_state(session)
+                ),
+                Style::default().fg(Color::Red),
+            ))
+            .block(popup_block)
+            .wrap(Wrap { trim: false });
+            let area = centered_rect(60, 25, frame.area());
+            frame.render_widget(Clear, area);
+            frame.render_widget(prompt, area);
+        }
+    }
+
+    if let CurrentScreen::Install = app.screen {
+        render_input(frame, "Path to a mod archive (.zip)", &app.path_input);
+    }
+
+    if let CurrentScreen::ConfirmInstall = app.screen {
+        render_install_plan(frame, app);
+    }
+
+    if let CurrentScreen::SetInbox = app.screen {
+        render_input(frame, "Downloads folder to watch", &app.path_input);
+    }
+
+    if let CurrentScreen::InboxPrompt = app.screen {
+        if let Some(archive) = app.inbox_queue.front() {
+            let file_name = match archive.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => archive.display().to_string(),
+            };
+            let popup_block = Block::default()
+                .title("New download")
+                .borders(Borders::NONE)
+                .style(Style::default().bg(Color::DarkGray));
+            let prompt = Paragraph::new(Text::styled(
+                format!(
+                    "Install {file_name} into instance {}?",
+                    app.current_instance
+                ),
+                Style::default().fg(Color::Yellow),
+            ))
+            .block(popup_block)
+            .wrap(Wrap { trim: false });
+            let area = centered_rect(60, 25, frame.area());
+            frame.render_widget(Clear, area);
+            frame.render_widget(prompt, area);
+        }
+    }
+
+    if let CurrentScreen::LockConflict = app.screen {
+        if let Some(lock) = &app.lock_conflict {
+            let popup_block = Block::default()
+                .title("Mods folder in use")
+                .borders(Borders::NONE)
+                .style(Style::default().bg(Color::DarkGray));
+            let prompt = Paragraph::new(Text::styled(
+                format!(
+                    "{} (pid {}) is already running from the mods folder of {}. Running both makes SMAPI overwrite each other's mod configs.",
+                    lock.instance, lock.pid, app.current_instance
+                ),
+                Style::default().fg(Color::Yellow),
+            ))
+            .block(popup_block)
+            .wrap(Wrap { trim: false });
+            let area = centered_rect(60, 25, frame.area());
+            frame.render_widget(Clear, area);
+            frame.render_widget(prompt, area);
+        }
     }
 
     if let CurrentScreen::Exit = app.screen {
@@ -171,7 +620,19 @@ pub fn ui(frame: &mut Frame, app: &App) {
             .borders(Borders::NONE)
             .style(Style::default().bg(Color::DarkGray));
 
-        let exit_text = Text::styled("Would you like to exit?", Style::default().fg(Color::Red));
+        let headless = app
+            .sessions
+            .values()
+            .filter(|session| session.headless && session.is_running())
+            .count();
+        let exit_text = if headless > 0 {
+            Text::styled(
+                format!("Would you like to exit? {headless} headless instance(s) will be stopped."),
+                Style::default().fg(Color::Red),
+            )
+        } else {
+            Text::styled("Would you like to exit?", Style::default().fg(Color::Red))
+        };
 
         let exit_paragraph = Paragraph::new(exit_text)
             .block(popup_block)
@@ -180,6 +641,601 @@ pub fn ui(frame: &mut Frame, app: &App) {
         let area = centered_rect(60, 25, frame.area());
         frame.render_widget(exit_paragraph, area);
     }
+
+    if let Some(error) = &app.error {
+        let popup_block = Block::default()
+            .title("Error - press any key to close")
+            .borders(Borders::ALL)
+            .style(Style::default().bg(Color::DarkGray));
+        let error_paragraph = Paragraph::new(Text::styled(
+            error.clone(),
+            Style::default().fg(Color::LightRed),
+        ))
+        .block(popup_block)
+        .wrap(Wrap { trim: false });
+        let area = centered_rect(60, 25, frame.area());
+        frame.render_widget(Clear, area);
+        frame.render_widget(error_paragraph, area);
+    }
+}
+
+fn render_setup(frame: &mut Frame, app: &App) {
+    let area = centered_rect(70, 60, frame.area());
+    frame.render_widget(Clear, area);
+    let popup_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(area);
+
+    let typing = !app.path_input.is_empty();
+    let mut lines = Vec::<Line>::new();
+    if app.setup_candidates.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No SMAPI installation found, enter the path to your game folder below",
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+    for (index, candidate) in app.setup_candidates.iter().enumerate() {
+        let (marker, color) = if index == app.setup_index && !typing {
+            ("> ", Color::Green)
+        } else {
+            ("  ", Color::White)
+        };
+        lines.push(Line::from(Span::styled(
+            format!("{marker}{}", candidate.display()),
+            Style::default().fg(color),
+        )));
+    }
+    let candidates = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Select your SMAPI installation")
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .wrap(Wrap { trim: false });
+    frame.render_widget(candidates, popup_chunks[0]);
+
+    let input_style = if typing {
+        Style::default().bg(Color::LightYellow).fg(Color::Black)
+    } else {
+        Style::default()
+    };
+    let input = Paragraph::new(app.path_input.clone()).block(
+        Block::default()
+            .title("Or enter the game folder or StardewModdingAPI path")
+            .borders(Borders::ALL)
+            .style(input_style),
+    );
+    frame.render_widget(input, popup_chunks[1]);
+}
+
+fn render_launch_settings(frame: &mut Frame, app: &App) {
+    let scope = if app.launch_global {
+        "all instances".to_string()
+    } else {
+        format!("instance {}", app.current_instance)
+    };
+    let popup_block = Block::default()
+        .title(format!("Launch settings for {scope}"))
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+
+    let draft = &app.launch_draft;
+    let terminal = match draft.terminal_preset() {
+        Some(index) => TERMINAL_PRESETS[index].0,
+        None => "custom",
+    };
+    let wrapper = match draft.wrapper_preset() {
+        Some(index) => WRAPPER_PRESETS[index].0,
+        None => "custom",
+    };
+    let mode = match draft.mode {
+        LaunchMode::Terminal => "terminal",
+        LaunchMode::Headless => "headless, output shown here",
+    };
+    let rows = [
+        format!("Terminal: < {terminal} >  {}", draft.terminal),
+        format!("Wrapper:  < {wrapper} >  {}", draft.command),
+        format!("Mode:     < {mode} >"),
+    ];
+    let mut lines = Vec::<Line>::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let (marker, color) = if index == app.launch_row {
+            ("> ", Color::Green)
+        } else {
+            ("  ", Color::White)
+        };
+        lines.push(Line::from(Span::styled(
+            format!("{marker}{row}"),
+            Style::default().fg(color),
+        )));
+    }
+
+    if let Some(instance) = app.manager.instances.get(&app.current_instance) {
+        let context = app
+            .manager
+            .launch_context(&app.current_instance, instance.clone());
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("Runs: {}", draft.args(&context).join(" ")),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .wrap(Wrap { trim: false });
+    let area = centered_rect(70, 40, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+fn session_state(session: &Session) -> String {
+    match &session.status {
+        SessionStatus::Running => format!(
+            "pid {}, running for {}",
+            session.pid(),
+            format_duration(session.elapsed())
+        ),
+        SessionStatus::Exited(status) => status.to_string(),
+        SessionStatus::Lost(e) => e.clone(),
+    }
+}
+
+fn viewer_area(area: Rect) -> Rect {
+    centered_rect(90, 80, area)
+}
+
+/// Number of lines that fit into the console and log popups.
+pub fn viewer_height(size: Size) -> usize {
+    let area = viewer_area(Rect::new(0, 0, size.width, size.height));
+    area.height.saturating_sub(2) as usize
+}
+
+fn level_color(level: LogLevel) -> Color {
+    match level {
+        LogLevel::Trace => Color::DarkGray,
+        LogLevel::Debug => Color::Gray,
+        LogLevel::Info => Color::White,
+        LogLevel::Warn => Color::Yellow,
+        LogLevel::Error => Color::Red,
+        LogLevel::Alert => Color::Magenta,
+    }
+}
+
+fn render_console(frame: &mut Frame, app: &App) {
+    let (title, console) = match app.current_session() {
+        Some(session) => (
+            format!(
+                "SMAPI output - {} ({})",
+                session.instance,
+                session_state(session)
+            ),
+            session.console.as_slice(),
+        ),
+        None => (format!("SMAPI output - {}", app.current_instance), &[][..]),
+    };
+    let area = viewer_area(frame.area());
+    let height = area.height.saturating_sub(2) as usize;
+    let last = console.len().saturating_sub(height);
+    let first = app.console_scroll.unwrap_or(last).min(last);
+
+    let lines: Vec<Line> = console
+        .iter()
+        .skip(first)
+        .take(height)
+        .map(|line| {
+            Line::from(Span::styled(
+                line.text.clone(),
+                Style::default().fg(level_color(line.level)),
+            ))
+        })
+        .collect();
+    let console = Paragraph::new(lines).block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(console, area);
+}
+
+fn render_log(frame: &mut Frame, app: &App) {
+    let log = match &app.log {
+        Some(log) => log,
+        None => return,
+    };
+    let file_name = match log.path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => log.path.display().to_string(),
+    };
+    let source = app.log_source.as_deref().unwrap_or("all mods");
+    let title = format!("{file_name} - {} and above, {source}", app.log_level.name());
+
+    let mut lines = Vec::<Line>::new();
+    for entry in log.filtered(app.log_level, app.log_source.as_deref()) {
+        let color = level_color(entry.level);
+        let mut message = entry.message.lines();
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{} ", entry.time),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::styled(
+                format!("{: <5} ", entry.level.name()),
+                Style::default().fg(color),
+            ),
+            Span::styled(
+                format!("{}: ", entry.source),
+                Style::default().fg(Color::Cyan),
+            ),
+            Span::styled(
+                message.next().unwrap_or_default().to_string(),
+                Style::default().fg(color),
+            ),
+        ]));
+        for continued in message {
+            lines.push(Line::from(Span::styled(
+                continued.to_string(),
+                Style::default().fg(color),
+            )));
+        }
+    }
+
+    let area = viewer_area(frame.area());
+    let height = area.height.saturating_sub(2) as usize;
+    let first = app.log_scroll.min(lines.len().saturating_sub(height));
+    let lines: Vec<Line> = lines.into_iter().skip(first).take(height).collect();
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+fn render_summary(frame: &mut Frame, app: &App) {
+    let summary = match app.history.get(app.history_index) {
+        Some(summary) => summary,
+        None => return,
+    };
+    let popup_block = Block::default()
+        .title(format!(
+            "Session of {} ({}/{})",
+            summary.instance,
+            app.history_index + 1,
+            app.history.len()
+        ))
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+
+    let duration = format_duration(Duration::from_secs(summary.duration_secs));
+    let (ended, color) = match summary.exit_code {
+        Some(0) => (format!("Exited normally after {duration}"), Color::Green),
+        Some(code) => (
+            format!("Exited with code {code} after {duration}"),
+            Color::Red,
+        ),
+        None => (format!("Stopped after {duration}"), Color::Yellow),
+    };
+    let mut lines = vec![
+        Line::from(Span::styled(ended, Style::default().fg(color))),
+        Line::from(Span::styled(
+            format!("Started {}", summary.started),
+            Style::default().fg(Color::White),
+        )),
+        Line::from(""),
+    ];
+
+    if summary.log.is_none() {
+        lines.push(Line::from(Span::styled(
+            "SMAPI wrote no log for this session",
+            Style::default().fg(Color::Yellow),
+        )));
+    }
+    if !summary.skipped_mods.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "Skipped mods:",
+            Style::default().fg(Color::Red),
+        )));
+        for skipped in &summary.skipped_mods {
+            lines.push(Line::from(Span::styled(
+                format!("  {skipped}"),
+                Style::default().fg(Color::White),
+            )));
+        }
+        lines.push(Line::from(""));
+    }
+    if summary.log.is_some() && summary.problems.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No errors or warnings",
+            Style::default().fg(Color::Green),
+        )));
+    }
+    for problems in &summary.problems {
+        let color = if problems.errors > 0 {
+            Color::Red
+        } else {
+            Color::Yellow
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{}: ", problems.source),
+                Style::default().fg(Color::White),
+            ),
+            Span::styled(
+                format!(
+                    "{} error(s), {} warning(s)",
+                    problems.errors, problems.warnings
+                ),
+                Style::default().fg(color),
+            ),
+        ]));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .wrap(Wrap { trim: false });
+    let area = centered_rect(70, 60, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+/// Saves last played by another instance than the selected one are yellow,
+/// so playing a farm with the wrong mods stands out.
+fn render_saves(frame: &mut Frame, app: &mut App) {
+    let mut rows = Vec::<Row>::new();
+    for save in &app.saves {
+        let (played_by, color) = match &save.played_by {
+            Some(played_by) if played_by.instance == app.current_instance => {
+                (played_by.instance.clone(), Color::Green)
+            }
+            Some(played_by) => (played_by.instance.clone(), Color::Yellow),
+            None => ("unknown".to_string(), Color::DarkGray),
+        };
+        rows.push(Row::new(vec![
+            Cell::from(save.farm_name.clone()).style(Style::default().fg(Color::White)),
+            Cell::from(save.player_name.clone()).style(Style::default().fg(Color::White)),
+            Cell::from(save.date()).style(Style::default().fg(Color::White)),
+            Cell::from(format_duration(save.played)).style(Style::default().fg(Color::White)),
+            Cell::from(save.game_version.clone()).style(Style::default().fg(Color::White)),
+            Cell::from(save.owner.clone().unwrap_or("shared".into()))
+                .style(Style::default().fg(Color::Cyan)),
+            Cell::from(played_by).style(Style::default().fg(color)),
+        ]));
+    }
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(17),
+            Constraint::Percentage(14),
+            Constraint::Percentage(17),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(16),
+            Constraint::Percentage(16),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Farm",
+            "Player",
+            "Date",
+            "Played",
+            "Version",
+            "Saves",
+            "Last played on",
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(
+        Block::default()
+            .title("Saves")
+            .borders(Borders::ALL)
+            .style(Style::default().bg(Color::DarkGray)),
+    )
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let area = viewer_area(frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut app.save_state);
+}
+
+fn render_backups(frame: &mut Frame, app: &mut App) {
+    let title = match app.backups.first() {
+        Some(backup) => format!("Backups of {} ({})", backup.save, backup.owner),
+        None => "Backups".to_string(),
+    };
+    let rows: Vec<Row> = app
+        .backups
+        .iter()
+        .map(|backup| {
+            Row::new(vec![
+                Cell::from(backup.time.format("%Y-%m-%d %H:%M:%S").to_string()),
+                Cell::from(format!("{} KiB", backup.size.div_ceil(1024))),
+            ])
+            .style(Style::default().fg(Color::White))
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [Constraint::Percentage(60), Constraint::Percentage(40)],
+    )
+    .header(Row::new(vec!["Taken", "Size"]).style(Style::default().add_modifier(Modifier::BOLD)))
+    .block(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .style(Style::default().bg(Color::DarkGray)),
+    )
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let area = centered_rect(50, 60, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut app.backup_state);
+}
+
+fn render_report(frame: &mut Frame, app: &App) {
+    let title = if app.launch_pending {
+        "Unresolved dependencies - launch anyway?"
+    } else {
+        "Instance report"
+    };
+    let popup_block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+
+    let mut lines = Vec::<Line>::new();
+    if app.report.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "No problems found",
+            Style::default().fg(Color::Green),
+        )));
+    }
+    for (index, duplicate) in app.report.duplicates.iter().enumerate() {
+        let marker = if index == app.duplicate_index {
+            "> "
+        } else {
+            "  "
+        };
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{marker}{} is installed {} times",
+                duplicate.unique_id,
+                duplicate.copies.len()
+            ),
+            Style::default().fg(Color::Red),
+        )));
+        for (copy_index, copy) in duplicate.copies.iter().enumerate() {
+            let label = if copy_index == 0 { "keep" } else { "older" };
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "    [{label}] {} at {}",
+                    copy.manifest.version,
+                    copy.path.display()
+                ),
+                Style::default().fg(Color::White),
+            )));
+        }
+    }
+    for issue in &app.report.dependency_issues {
+        let minimum = match &issue.minimum_version {
+            Some(version) => format!(" >= {version}"),
+            None => String::new(),
+        };
+        let (text, color) = match &issue.problem {
+            DependencyProblem::Missing => (
+                format!(
+                    "requires {}{minimum}, which is not installed",
+                    issue.dependency_id
+                ),
+                Color::Red,
+            ),
+            DependencyProblem::Outdated { installed } => (
+                format!(
+                    "requires {}{minimum}, but {installed} is installed",
+                    issue.dependency_id
+                ),
+                Color::Red,
+            ),
+            DependencyProblem::OptionalMissing => (
+                format!(
+                    "can use {}{minimum}, which is not installed",
+                    issue.dependency_id
+                ),
+                Color::Yellow,
+            ),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{}: ", issue.mod_name),
+                Style::default().fg(Color::White),
+            ),
+            Span::styled(text, Style::default().fg(color)),
+        ]));
+    }
+
+    let report = Paragraph::new(lines)
+        .block(popup_block)
+        .wrap(Wrap { trim: false })
+        .scroll((app.report_scroll, 0));
+
+    let area = centered_rect(80, 70, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(report, area);
+}
+
+/// Draws a progress bar on its own, for long operations that block the event loop.
+pub fn progress(frame: &mut Frame, title: &str, done: usize, total: usize) {
+    let ratio = if total == 0 {
+        1.0
+    } else {
+        done as f64 / total as f64
+    };
+    let gauge = Gauge::default()
+        .block(
+            Block::default()
+                .title(title.to_string())
+                .borders(Borders::ALL),
+        )
+        .gauge_style(Style::default().fg(Color::Green))
+        .label(format!("{done}/{total} files"))
+        .ratio(ratio);
+    let area = centered_rect(60, 20, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(gauge, area);
+}
+
+fn render_input(frame: &mut Frame, title: &str, value: &str) {
+    let area = centered_rect(60, 20, frame.area());
+    frame.render_widget(Clear, area);
+    let popup_block = Block::default()
+        .title(title.to_string())
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::LightYellow).fg(Color::Black));
+    let input = Paragraph::new(value.to_string()).block(popup_block);
+    frame.render_widget(input, area);
+}
+
+fn render_install_plan(frame: &mut Frame, app: &App) {
+    let plan = match &app.install_plan {
+        Some(plan) => plan,
+        None => return,
+    };
+    let popup_block = Block::default()
+        .title(format!("Install into {}?", app.current_instance))
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+
+    let mut lines = Vec::<Line>::new();
+    for archive_mod in &plan.mods {
+        let manifest = &archive_mod.manifest;
+        let text = match &archive_mod.existing {
+            Some(existing) => Span::styled(
+                format!(
+                    "{}: upgrade {} -> {} (keeps config.json)",
+                    manifest.name, existing.manifest.version, manifest.version
+                ),
+                Style::default().fg(Color::Yellow),
+            ),
+            None => Span::styled(
+                format!("{}: install {}", manifest.name, manifest.version),
+                Style::default().fg(Color::Green),
+            ),
+        };
+        lines.push(Line::from(text));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(popup_block)
+        .wrap(Wrap { trim: false });
+    let area = centered_rect(70, 50, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(paragraph, area);
 }
 
 fn centered_rect(percentage_x: u16, percentage_y: u16, r: Rect) -> Rect {